@@ -1,372 +1,311 @@
 use aes::cipher::{BlockDecrypt, BlockEncrypt, KeyInit, KeyIvInit};
 use aes::Aes256;
 use cbc::cipher::{BlockDecryptMut, BlockEncryptMut};
-use pyo3::prelude::*;
-use pyo3::types::{PyBytes, PyByteArray};
+
+use crate::error::{Error, Result};
 
 #[inline]
 fn xor_blocks(a: &mut [u8; 16], b: &[u8; 16]) {
-    for i in 0..16 {
-        a[i] ^= b[i];
+    for (x, y) in a.iter_mut().zip(b) {
+        *x ^= y;
     }
 }
 
-/// AES-256-IGE Encryption
-#[pyfunction]
-#[pyo3(signature = (data, key, iv, /))]
-pub fn ige256_encrypt<'py>(
-    py: Python<'py>,
-    data: &[u8],
-    key: Bound<'py, PyAny>,
-    iv: Bound<'py, PyAny>,
-) -> PyResult<Bound<'py, PyBytes>> {
-    // Convert key (bytes or bytearray) to Vec<u8>
-    let key_bytes: Vec<u8> = key.extract()?;
-    if key_bytes.len() != 32 {
-        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
-            "Key must be 32 bytes",
-        ));
+fn check_key(key: &[u8]) -> Result<()> {
+    if key.len() != 32 {
+        return Err(Error::InvalidKeyLength { expected: 32, actual: key.len() });
     }
+    Ok(())
+}
 
-    // Convert iv (bytes or bytearray) to Vec<u8>
-    let iv_bytes: Vec<u8> = iv.extract()?;
-    if iv_bytes.len() != 32 {
-        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
-            "IV must be 32 bytes",
-        ));
+fn check_iv(iv: &[u8], expected: usize) -> Result<()> {
+    if iv.len() != expected {
+        return Err(Error::InvalidIvLength { expected, actual: iv.len() });
     }
+    Ok(())
+}
 
-    if data.len() % 16 != 0 {
-        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
-            "Data length must be a multiple of 16",
-        ));
+fn check_blocks(data: &[u8]) -> Result<()> {
+    if !data.len().is_multiple_of(16) {
+        return Err(Error::InvalidDataLength);
     }
+    Ok(())
+}
 
-    let result = py.detach(|| {
-        let cipher = Aes256::new(key_bytes.as_slice().into());
-        // For encryption: iv1 = iv[0:16], iv2 = iv[16:32]
-        let mut iv1: [u8; 16] = iv_bytes[..16].try_into().unwrap();
-        let mut iv2: [u8; 16] = iv_bytes[16..].try_into().unwrap();
-
-        let mut result = vec![0u8; data.len()];
-
-        for (i, chunk) in data.chunks(16).enumerate() {
-            let mut block: [u8; 16] = chunk.try_into().unwrap();
-            let old_input = block;  // Save original input for iv2 update
-
-            // XOR with iv1
-            xor_blocks(&mut block, &iv1);
-            // AES encrypt
-            cipher.encrypt_block((&mut block).into());
-            // XOR with iv2
-            xor_blocks(&mut block, &iv2);
+/// IGE-encrypt whole blocks in place, carrying (iv1, iv2) forward
+fn ige_encrypt_blocks(
+    cipher: &Aes256,
+    data: &mut [u8],
+    iv1: &mut [u8; 16],
+    iv2: &mut [u8; 16],
+) {
+    for chunk in data.chunks_exact_mut(16) {
+        let mut block: [u8; 16] = (&*chunk).try_into().unwrap();
+        let old_input = block; // Save original input for iv2 update
+
+        // XOR with iv1
+        xor_blocks(&mut block, iv1);
+        // AES encrypt
+        cipher.encrypt_block((&mut block).into());
+        // XOR with iv2
+        xor_blocks(&mut block, iv2);
+
+        chunk.copy_from_slice(&block);
+
+        // Update IVs: iv1 = output, iv2 = original input
+        *iv1 = block;
+        *iv2 = old_input;
+    }
+}
 
-            result[i*16..(i+1)*16].copy_from_slice(&block);
+/// IGE-decrypt whole blocks in place, carrying (iv1, iv2) forward
+fn ige_decrypt_blocks(
+    cipher: &Aes256,
+    data: &mut [u8],
+    iv1: &mut [u8; 16],
+    iv2: &mut [u8; 16],
+) {
+    for chunk in data.chunks_exact_mut(16) {
+        let mut block: [u8; 16] = (&*chunk).try_into().unwrap();
+        let old_input = block; // Save original input (ciphertext) for iv2 update
+
+        // XOR with iv1 first (matches C implementation)
+        xor_blocks(&mut block, iv1);
+        // AES decrypt
+        cipher.decrypt_block((&mut block).into());
+        // XOR with iv2
+        xor_blocks(&mut block, iv2);
+
+        chunk.copy_from_slice(&block);
+
+        // Update IVs: iv1 = output (decrypted), iv2 = original input (ciphertext)
+        // This matches the C implementation: memcpy(iv1, &out[i], ...); memcpy(iv2, chunk, ...);
+        *iv1 = block;
+        *iv2 = old_input;
+    }
+}
 
-            // Update IVs: iv1 = output, iv2 = original input
-            iv1 = block;
-            iv2 = old_input;
+/// Apply the CTR keystream in place
+/// `pos` is the position in the current keystream block, as in pyaes
+fn ctr_apply(cipher: &Aes256, data: &mut [u8], counter: &mut [u8; 16], pos: &mut usize) {
+    let mut keystream = *counter;
+    cipher.encrypt_block((&mut keystream).into());
+
+    for byte in data.iter_mut() {
+        *byte ^= keystream[*pos];
+        *pos += 1;
+
+        if *pos >= 16 {
+            *pos = 0;
+            // Increment IV as big-endian 128-bit counter
+            for j in (0..16).rev() {
+                counter[j] = counter[j].wrapping_add(1);
+                if counter[j] != 0 {
+                    break;
+                }
+            }
+            keystream = *counter;
+            cipher.encrypt_block((&mut keystream).into());
         }
+    }
+}
 
-        result
-    });
-
-    Ok(PyBytes::new(py, &result))
+/// AES-256-IGE with a cached key schedule
+#[derive(Clone)]
+pub struct Ige256 {
+    cipher: Aes256,
 }
 
-/// AES-256-IGE Decryption
-#[pyfunction]
-#[pyo3(signature = (data, key, iv, /))]
-pub fn ige256_decrypt<'py>(
-    py: Python<'py>,
-    data: &[u8],
-    key: Bound<'py, PyAny>,
-    iv: Bound<'py, PyAny>,
-) -> PyResult<Bound<'py, PyBytes>> {
-    // Convert key (bytes or bytearray) to Vec<u8>
-    let key_bytes: Vec<u8> = key.extract()?;
-    if key_bytes.len() != 32 {
-        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
-            "Key must be 32 bytes",
-        ));
+impl Ige256 {
+    pub fn new(key: &[u8]) -> Result<Self> {
+        check_key(key)?;
+        Ok(Self { cipher: Aes256::new(key.into()) })
     }
 
-    // Convert iv (bytes or bytearray) to Vec<u8>
-    let iv_bytes: Vec<u8> = iv.extract()?;
-    if iv_bytes.len() != 32 {
-        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
-            "IV must be 32 bytes",
-        ));
+    /// Encrypt whole blocks in place under a 32-byte IV
+    pub fn encrypt(&self, data: &mut [u8], iv: &[u8]) -> Result<()> {
+        self.encryptor(iv)?.update(data)
     }
 
-    if data.len() % 16 != 0 {
-        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
-            "Data length must be a multiple of 16",
-        ));
+    /// Decrypt whole blocks in place under a 32-byte IV
+    pub fn decrypt(&self, data: &mut [u8], iv: &[u8]) -> Result<()> {
+        self.decryptor(iv)?.update(data)
     }
 
-    let result = py.detach(|| {
-        let cipher = Aes256::new(key_bytes.as_slice().into());
-        // For decryption: iv2 = iv[0:16], iv1 = iv[16:32] (SWAPPED!)
-        let mut iv1: [u8; 16] = iv_bytes[16..].try_into().unwrap();
-        let mut iv2: [u8; 16] = iv_bytes[..16].try_into().unwrap();
-
-        let mut result = vec![0u8; data.len()];
-
-        for (i, chunk) in data.chunks(16).enumerate() {
-            let block: [u8; 16] = chunk.try_into().unwrap();
-            let old_input = block;  // Save original input (ciphertext) for iv1 update
+    /// Start a resumable encryption that can be fed in chunks
+    pub fn encryptor(&self, iv: &[u8]) -> Result<IgeStream> {
+        check_iv(iv, 32)?;
+        // For encryption: iv1 = iv[0:16], iv2 = iv[16:32]
+        Ok(IgeStream {
+            cipher: self.cipher.clone(),
+            iv1: iv[..16].try_into().unwrap(),
+            iv2: iv[16..].try_into().unwrap(),
+            decrypt: false,
+        })
+    }
 
-            let mut decrypted = block;
-            // XOR with iv1 first (matches C implementation)
-            xor_blocks(&mut decrypted, &iv1);
-            // AES decrypt
-            cipher.decrypt_block((&mut decrypted).into());
-            // XOR with iv2
-            xor_blocks(&mut decrypted, &iv2);
+    /// Start a resumable decryption that can be fed in chunks
+    pub fn decryptor(&self, iv: &[u8]) -> Result<IgeStream> {
+        check_iv(iv, 32)?;
+        // For decryption: iv2 = iv[0:16], iv1 = iv[16:32] (SWAPPED!)
+        Ok(IgeStream {
+            cipher: self.cipher.clone(),
+            iv1: iv[16..].try_into().unwrap(),
+            iv2: iv[..16].try_into().unwrap(),
+            decrypt: true,
+        })
+    }
+}
 
-            result[i*16..(i+1)*16].copy_from_slice(&decrypted);
+/// AES-256-IGE state carrying the (iv1, iv2) pair across chunks
+#[derive(Clone)]
+pub struct IgeStream {
+    cipher: Aes256,
+    iv1: [u8; 16],
+    iv2: [u8; 16],
+    decrypt: bool,
+}
 
-            // Update IVs: iv1 = output (decrypted), iv2 = original input (ciphertext)
-            // This matches the C implementation: memcpy(iv1, &out[i], ...); memcpy(iv2, chunk, ...);
-            iv1 = decrypted;
-            iv2 = old_input;
+impl IgeStream {
+    /// Process the next chunk in place, its length must be a multiple of 16
+    pub fn update(&mut self, data: &mut [u8]) -> Result<()> {
+        check_blocks(data)?;
+        if self.decrypt {
+            ige_decrypt_blocks(&self.cipher, data, &mut self.iv1, &mut self.iv2);
+        } else {
+            ige_encrypt_blocks(&self.cipher, data, &mut self.iv1, &mut self.iv2);
         }
+        Ok(())
+    }
 
-        result
-    });
+    /// Current 32-byte IV, laid out like the IV the stream was started with
+    pub fn iv(&self) -> [u8; 32] {
+        let (first, second) = if self.decrypt { (&self.iv2, &self.iv1) } else { (&self.iv1, &self.iv2) };
+        let mut iv = [0u8; 32];
+        iv[..16].copy_from_slice(first);
+        iv[16..].copy_from_slice(second);
+        iv
+    }
+}
 
-    Ok(PyBytes::new(py, &result))
+/// AES-256-IGE Encryption
+pub fn ige256_encrypt(data: &mut [u8], key: &[u8], iv: &[u8]) -> Result<()> {
+    Ige256::new(key)?.encrypt(data, iv)
 }
 
-/// AES-256-CTR Encryption/Decryption
-/// This matches the pyaes implementation where state[0] is the position in the keystream block
-#[pyfunction]
-#[pyo3(signature = (data, key, iv, state, /))]
-pub fn ctr256_encrypt<'py>(
-    py: Python<'py>,
-    data: Bound<'py, PyAny>,
-    key: Bound<'py, PyAny>,
-    iv: Bound<'py, PyAny>,
-    state: Bound<'py, PyAny>,
-) -> PyResult<Bound<'py, PyBytes>> {
-    // Convert key (bytes or bytearray) to Vec<u8>
-    let key_bytes: Vec<u8> = key.extract()?;
-    if key_bytes.len() != 32 {
-        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
-            "Key must be 32 bytes",
-        ));
-    }
+/// AES-256-IGE Decryption
+pub fn ige256_decrypt(data: &mut [u8], key: &[u8], iv: &[u8]) -> Result<()> {
+    Ige256::new(key)?.decrypt(data, iv)
+}
 
-    // Convert data (bytes or bytearray) to Vec<u8>
-    let data_bytes: Vec<u8> = data.extract()?;
+/// AES-256-CTR stream owning its counter and keystream position
+#[derive(Clone)]
+pub struct CtrStream {
+    cipher: Aes256,
+    iv: [u8; 16],
+    counter: [u8; 16],
+    pos: usize,
+    offset: u64,
+}
 
-    // Convert iv (bytes or bytearray) to Vec<u8>
-    let iv_bytes: Vec<u8> = iv.extract()?;
-    if iv_bytes.len() != 16 {
-        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
-            "IV must be 16 bytes",
-        ));
+impl CtrStream {
+    /// Start a stream at `offset` bytes into the keystream of (key, iv)
+    pub fn new(key: &[u8], iv: &[u8], offset: u64) -> Result<Self> {
+        check_key(key)?;
+        check_iv(iv, 16)?;
+
+        let iv: [u8; 16] = iv.try_into().unwrap();
+        let mut stream = Self {
+            cipher: Aes256::new(key.into()),
+            iv,
+            counter: iv,
+            pos: 0,
+            offset: 0,
+        };
+        stream.seek(offset);
+        Ok(stream)
     }
 
-    // Extract state position (state[0] is position in keystream block 0-15)
-    let state_bytes: Vec<u8> = state.extract().unwrap_or_default();
-    let mut ks_pos = if !state_bytes.is_empty() { state_bytes[0] as usize } else { 0 };
-
-    let out = py.detach(|| {
-        let cipher = Aes256::new(key_bytes.as_slice().into());
-        let mut out = data_bytes;
-        let mut iv_array: [u8; 16] = iv_bytes.try_into().unwrap();
-        let mut keystream = [0u8; 16];
-        let mut keystream_valid = false;
-
-        // Optimized loop: process full blocks if aligned
-        // For now, keep the byte loop but it can be optimized
-        // Let's at least avoid the keystream generation check every byte if possible
-        
-        for i in 0..out.len() {
-            if ks_pos == 0 || !keystream_valid {
-                keystream = iv_array;
-                cipher.encrypt_block((&mut keystream).into());
-                keystream_valid = true;
-            }
-
-            out[i] ^= keystream[ks_pos];
-            ks_pos += 1;
-
-            if ks_pos >= 16 {
-                ks_pos = 0;
-                // Increment IV as big-endian 128-bit counter
-                // Unrolling or optimizing this loop could help slightly
-                for j in (0..16).rev() {
-                    iv_array[j] = iv_array[j].wrapping_add(1);
-                    if iv_array[j] != 0 {
-                        break;
-                    }
-                }
-                keystream_valid = false;
-            }
-        }
-
-        (out, iv_array, ks_pos)
-    });
+    /// Encrypt or decrypt the next bytes of the stream in place
+    pub fn process(&mut self, data: &mut [u8]) {
+        ctr_apply(&self.cipher, data, &mut self.counter, &mut self.pos);
+        self.offset = self.offset.wrapping_add(data.len() as u64);
+    }
 
-    let (out_bytes, final_iv, final_state) = out;
+    /// Move to an absolute byte offset in the keystream
+    pub fn seek(&mut self, offset: u64) {
+        let block = u128::from_be_bytes(self.iv).wrapping_add((offset / 16) as u128);
+        self.counter = block.to_be_bytes();
+        self.pos = (offset % 16) as usize;
+        self.offset = offset;
+    }
 
-    // Update state bytearray
-    if let Ok(state_bytes_obj) = state.cast_into::<PyByteArray>() {
-        if state_bytes_obj.len() > 0 {
-            let slice = unsafe { state_bytes_obj.as_bytes_mut() };
-            slice[0] = final_state as u8;
-        }
+    /// Current byte offset in the keystream
+    pub fn tell(&self) -> u64 {
+        self.offset
     }
+}
 
-    // Update IV bytearray
-    if let Ok(iv_bytes_obj) = iv.cast_into::<PyByteArray>() {
-        if iv_bytes_obj.len() == 16 {
-            let slice = unsafe { iv_bytes_obj.as_bytes_mut() };
-            slice.copy_from_slice(&final_iv);
-        }
+/// AES-256-CTR Encryption/Decryption
+/// `iv` is advanced as the counter and `state` is the position in the keystream block
+pub fn ctr256_encrypt(data: &mut [u8], key: &[u8], iv: &mut [u8], state: &mut u8) -> Result<()> {
+    check_key(key)?;
+    check_iv(iv, 16)?;
+    if *state >= 16 {
+        return Err(Error::InvalidState(*state as usize));
     }
 
-    Ok(PyBytes::new(py, &out_bytes))
+    let cipher = Aes256::new(key.into());
+    let mut counter: [u8; 16] = (&*iv).try_into().unwrap();
+    let mut pos = *state as usize;
+    ctr_apply(&cipher, data, &mut counter, &mut pos);
+
+    iv.copy_from_slice(&counter);
+    *state = pos as u8;
+    Ok(())
 }
 
 /// AES-256-CTR Decryption
-#[pyfunction]
-#[pyo3(signature = (data, key, iv, state, /))]
-pub fn ctr256_decrypt<'py>(
-    py: Python<'py>,
-    data: Bound<'py, PyAny>,
-    key: Bound<'py, PyAny>,
-    iv: Bound<'py, PyAny>,
-    state: Bound<'py, PyAny>,
-) -> PyResult<Bound<'py, PyBytes>> {
+pub fn ctr256_decrypt(data: &mut [u8], key: &[u8], iv: &mut [u8], state: &mut u8) -> Result<()> {
     // CTR mode is symmetric
-    ctr256_encrypt(py, data, key, iv, state)
+    ctr256_encrypt(data, key, iv, state)
 }
 
 /// AES-256-CBC Encryption
-#[pyfunction]
-#[pyo3(signature = (data, key, iv, /))]
-pub fn cbc256_encrypt<'py>(
-    py: Python<'py>,
-    data: &[u8],
-    key: Bound<'py, PyAny>,
-    iv: Bound<'py, PyAny>,
-) -> PyResult<Bound<'py, PyBytes>> {
-    // Convert key (bytes or bytearray) to Vec<u8>
-    let key_bytes: Vec<u8> = key.extract()?;
-    if key_bytes.len() != 32 {
-        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
-            "Key must be 32 bytes",
-        ));
+/// `iv` is updated to the last ciphertext block
+pub fn cbc256_encrypt(data: &mut [u8], key: &[u8], iv: &mut [u8]) -> Result<()> {
+    check_key(key)?;
+    check_iv(iv, 16)?;
+    check_blocks(data)?;
+
+    let mut cipher = cbc::Encryptor::<Aes256>::new_from_slices(key, iv).unwrap();
+    for chunk in data.chunks_exact_mut(16) {
+        cipher.encrypt_block_mut(chunk.into());
     }
 
-    // Convert iv (bytes or bytearray) to Vec<u8>
-    let iv_bytes: Vec<u8> = iv.extract()?;
-    if iv_bytes.len() != 16 {
-        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
-            "IV must be 16 bytes",
-        ));
+    // Final IV is the last ciphertext block
+    if !data.is_empty() {
+        iv.copy_from_slice(&data[data.len() - 16..]);
     }
-
-    if data.len() % 16 != 0 {
-        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
-            "Data length must be a multiple of 16",
-        ));
-    }
-
-    let (result, final_iv) = py.detach(|| {
-        let mut iv_array: [u8; 16] = iv_bytes.try_into().unwrap();
-        let mut cipher = cbc::Encryptor::<Aes256>::new_from_slices(&key_bytes, &iv_array).unwrap();
-        
-        // Single copy: Input -> Result Vec
-        let mut result = data.to_vec();
-        
-        // Process in place
-        for chunk in result.chunks_mut(16) {
-            cipher.encrypt_block_mut(chunk.into());
-        }
-        
-        // Final IV is the last ciphertext block
-        if !result.is_empty() {
-            iv_array.copy_from_slice(&result[result.len() - 16..]);
-        }
-        
-        (result, iv_array)
-    });
-
-    // Update IV bytearray
-    if let Ok(iv_bytes_obj) = iv.cast_into::<PyByteArray>() {
-        if iv_bytes_obj.len() == 16 {
-            let slice = unsafe { iv_bytes_obj.as_bytes_mut() };
-            slice.copy_from_slice(&final_iv);
-        }
-    }
-
-    Ok(PyBytes::new(py, &result))
+    Ok(())
 }
 
 /// AES-256-CBC Decryption
-#[pyfunction]
-#[pyo3(signature = (data, key, iv, /))]
-pub fn cbc256_decrypt<'py>(
-    py: Python<'py>,
-    data: &[u8],
-    key: Bound<'py, PyAny>,
-    iv: Bound<'py, PyAny>,
-) -> PyResult<Bound<'py, PyBytes>> {
-    // Convert key (bytes or bytearray) to Vec<u8>
-    let key_bytes: Vec<u8> = key.extract()?;
-    if key_bytes.len() != 32 {
-        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
-            "Key must be 32 bytes",
-        ));
-    }
+/// `iv` is updated to the last ciphertext block
+pub fn cbc256_decrypt(data: &mut [u8], key: &[u8], iv: &mut [u8]) -> Result<()> {
+    check_key(key)?;
+    check_iv(iv, 16)?;
+    check_blocks(data)?;
 
-    // Convert iv (bytes or bytearray) to Vec<u8>
-    let iv_bytes: Vec<u8> = iv.extract()?;
-    if iv_bytes.len() != 16 {
-        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
-            "IV must be 16 bytes",
-        ));
-    }
+    let mut cipher = cbc::Decryptor::<Aes256>::new_from_slices(key, iv).unwrap();
 
-    if data.len() % 16 != 0 {
-        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
-            "Data length must be a multiple of 16",
-        ));
+    // Final IV is the last ciphertext block (from original data)
+    if !data.is_empty() {
+        iv.copy_from_slice(&data[data.len() - 16..]);
     }
 
-    let (result, final_iv) = py.detach(|| {
-        let iv_array: [u8; 16] = iv_bytes.try_into().unwrap();
-        let mut cipher = cbc::Decryptor::<Aes256>::new_from_slices(&key_bytes, &iv_array).unwrap();
-        
-        // Final IV is the last ciphertext block (from original data)
-        let mut last_iv = [0u8; 16];
-        if !data.is_empty() {
-            last_iv.copy_from_slice(&data[data.len() - 16..]);
-        } else {
-            last_iv = iv_array;
-        }
-
-        // Single copy: Input -> Result Vec
-        let mut result = data.to_vec();
-        for chunk in result.chunks_mut(16) {
-            cipher.decrypt_block_mut(chunk.into());
-        }
-        
-        (result, last_iv)
-    });
-
-    // Update IV bytearray
-    if let Ok(iv_bytes_obj) = iv.cast_into::<PyByteArray>() {
-        if iv_bytes_obj.len() == 16 {
-            let slice = unsafe { iv_bytes_obj.as_bytes_mut() };
-            slice.copy_from_slice(&final_iv);
-        }
+    for chunk in data.chunks_exact_mut(16) {
+        cipher.decrypt_block_mut(chunk.into());
     }
-
-    Ok(PyBytes::new(py, &result))
+    Ok(())
 }
@@ -0,0 +1,114 @@
+use std::collections::HashSet;
+use std::sync::RwLock;
+
+use num_bigint::BigUint;
+use num_integer::Integer;
+use num_traits::One;
+
+use crate::error::{Error, Result};
+use crate::factorization::is_prime;
+
+/// Size in bytes of dh_prime, g_a, g_b and the resulting auth_key
+pub const DH_PRIME_LEN: usize = 256;
+
+/// dh_prime sent by the production servers
+const TELEGRAM_DH_PRIME: &[u8] = b"C71CAEB9C6B1C9048E6C522F70F13F73980D40238E3E21C14934D037563D930F48198A0AA7C14058229493D22530F4DBFA336F6E0AC925139543AED44CCE7C3720FD51F69458705AC68CD4FE6B6B13ABDC9746512969328454F18FAF8C595F642477FE96BB2A941D5BCD1D4AC8CC49880708FA9B378E3C4F3A9060BEE67CF9A4A4A695811051907E162753B56B0F6B410DBA74D8A84B2A14B3144E0EF1284754FD17ED950D5965B4B9DD46582DB1178D169C6BC465B0D6FF9CA3928FEF5B9AE4E418FC15E83EBEA0F87FA9FF5EED70050DED2849F47BF959D956850CE929851F0D8115F635B105EE2E4E15D04B2454BF6F4FADF034B10403119CD8E3B92FCC5B";
+
+lazy_static::lazy_static! {
+    /// Primes that already passed `check_dh_prime`, so the primality tests only run once per prime
+    static ref KNOWN_PRIMES: RwLock<HashSet<BigUint>> = RwLock::new(
+        [BigUint::parse_bytes(TELEGRAM_DH_PRIME, 16).unwrap()].into_iter().collect()
+    );
+}
+
+/// Whether both p and (p - 1) / 2 are prime
+pub fn is_safe_prime(p: &BigUint) -> bool {
+    let q: BigUint = p >> 1;
+    p.is_odd() && is_prime(&q) && is_prime(p)
+}
+
+/// Parse dh_prime and check that it is a 2048-bit safe prime
+fn dh_prime(dh_prime: &[u8]) -> Result<BigUint> {
+    let p = BigUint::from_bytes_be(dh_prime);
+    if p.bits() != 2048 {
+        return Err(Error::InvalidDhPrime);
+    }
+    if KNOWN_PRIMES.read().unwrap().contains(&p) {
+        return Ok(p);
+    }
+    if !is_safe_prime(&p) {
+        return Err(Error::InvalidDhPrime);
+    }
+    KNOWN_PRIMES.write().unwrap().insert(p.clone());
+    Ok(p)
+}
+
+/// Check that dh_prime is a 2048-bit safe prime
+pub fn check_dh_prime(dh_prime_bytes: &[u8]) -> Result<()> {
+    dh_prime(dh_prime_bytes).map(|_| ())
+}
+
+/// Check that g generates the subgroup of order (p - 1) / 2, which depends on p modulo small numbers
+fn check_generator(g: u32, p: &BigUint) -> Result<()> {
+    let residue = |m: u32| (p % m).to_u32_digits().first().copied().unwrap_or(0);
+    let valid = match g {
+        2 => residue(8) == 7,
+        3 => residue(3) == 2,
+        4 => true,
+        5 => matches!(residue(5), 1 | 4),
+        6 => matches!(residue(24), 19 | 23),
+        7 => matches!(residue(7), 3 | 5 | 6),
+        _ => false,
+    };
+    if valid {
+        Ok(())
+    } else {
+        Err(Error::InvalidDhGenerator(g))
+    }
+}
+
+/// Check that dh_prime is a 2048-bit safe prime and that g is a valid generator for it
+pub fn check_dh_params(dh_prime_bytes: &[u8], g: u32) -> Result<()> {
+    check_generator(g, &dh_prime(dh_prime_bytes)?)
+}
+
+/// Check that g_a (or g_b) lies in [2^(2048-64), p - 2^(2048-64)]
+fn check_range(value: &BigUint, p: &BigUint) -> Result<()> {
+    let bound = BigUint::one() << (2048 - 64);
+    if *value < bound || *value > p - &bound {
+        return Err(Error::DhValueOutOfRange);
+    }
+    Ok(())
+}
+
+/// Check that g_a (or g_b) is safely inside the group defined by dh_prime
+pub fn check_g_a(g_a: &[u8], dh_prime_bytes: &[u8]) -> Result<()> {
+    check_range(&BigUint::from_bytes_be(g_a), &dh_prime(dh_prime_bytes)?)
+}
+
+/// Left-pad a value below dh_prime to 256 big-endian bytes
+fn to_bytes(value: &BigUint) -> [u8; DH_PRIME_LEN] {
+    let bytes = value.to_bytes_be();
+    let mut out = [0u8; DH_PRIME_LEN];
+    out[DH_PRIME_LEN - bytes.len()..].copy_from_slice(&bytes);
+    out
+}
+
+/// Compute g_b = g^b mod dh_prime after validating the parameters and the result
+pub fn compute_g_b(g: u32, b: &[u8], dh_prime_bytes: &[u8]) -> Result<[u8; DH_PRIME_LEN]> {
+    let p = dh_prime(dh_prime_bytes)?;
+    check_generator(g, &p)?;
+
+    let g_b = BigUint::from(g).modpow(&BigUint::from_bytes_be(b), &p);
+    check_range(&g_b, &p)?;
+    Ok(to_bytes(&g_b))
+}
+
+/// Compute auth_key = g_a^b mod dh_prime after checking that g_a is in range
+pub fn compute_auth_key(g_a: &[u8], b: &[u8], dh_prime_bytes: &[u8]) -> Result<[u8; DH_PRIME_LEN]> {
+    let p = dh_prime(dh_prime_bytes)?;
+    let g_a = BigUint::from_bytes_be(g_a);
+    check_range(&g_a, &p)?;
+
+    Ok(to_bytes(&g_a.modpow(&BigUint::from_bytes_be(b), &p)))
+}
@@ -0,0 +1,174 @@
+use std::fmt;
+
+/// Errors returned by the tgcrypto primitives
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Error {
+    /// Key does not have the length required by the cipher
+    InvalidKeyLength { expected: usize, actual: usize },
+    /// IV does not have the length required by the cipher mode
+    InvalidIvLength { expected: usize, actual: usize },
+    /// Data length is not a multiple of the AES block size
+    InvalidDataLength,
+    /// CTR state buffer is not exactly one byte
+    InvalidStateLength(usize),
+    /// CTR keystream position is outside of a 16-byte block
+    InvalidState(usize),
+    /// No server public key is known for this fingerprint
+    UnknownFingerprint(i64),
+    /// Auth key is not 256 bytes long
+    InvalidAuthKeyLength(usize),
+    /// KDF offset `x` is neither 0 (client to server) nor 8 (server to client)
+    InvalidDirection(usize),
+    /// Encrypted packet is too short or not block aligned
+    InvalidPacketLength(usize),
+    /// Packet was encrypted under a different auth key
+    AuthKeyIdMismatch,
+    /// Recomputed msg_key does not match the one in the packet
+    MsgKeyMismatch,
+    /// Message padding is outside of 12..=1024 bytes
+    BadPadding(usize),
+    /// message_data_length is unaligned or exceeds the payload
+    BadMessageLength(usize),
+    /// Message belongs to a different session
+    SessionMismatch,
+    /// msg_id has the wrong parity or is outside of the accepted time window
+    BadMsgId(i64),
+    /// Protocol tag is not one of the abridged/intermediate/padded intermediate tags
+    InvalidProtocolTag,
+    /// MTProxy secret has the wrong length
+    InvalidSecretLength(usize),
+    /// Init header starts with a forbidden prefix
+    InvalidInitPacket,
+    /// Fake-TLS secret is not 0xee + 16-byte key + domain
+    InvalidFakeTlsSecret,
+    /// Server response does not look like ServerHello + ChangeCipherSpec + application data
+    BadServerHello,
+    /// ServerHello random is not the expected HMAC
+    ServerHelloMismatch,
+    /// Received a TLS record other than application data or ChangeCipherSpec
+    BadTlsRecord,
+    /// Client data does not match any of the configured MTProxy secrets
+    UnknownSecret,
+    /// Client's first record is not a complete ClientHello
+    BadClientHello,
+    /// ClientHello timestamp is outside of the accepted window
+    ClientHelloExpired(u32),
+    /// Obfuscated2 init header has not been received from the client yet
+    InitPacketPending,
+    /// Transport packet length exceeds the accepted maximum
+    PacketTooLarge(usize),
+    /// Abridged transport packet length is not a multiple of 4
+    UnalignedPacket(usize),
+    /// Full transport CRC32 trailer does not match
+    BadCrc,
+    /// Full transport seq_no is not the next expected one
+    BadSeqNo(u32),
+    /// Data does not fit in the RSA padding scheme
+    DataTooLong { max: usize, actual: usize },
+    /// RSA input is not below the key's modulus
+    DataExceedsModulus,
+    /// PEM block is not a well-formed RSA public key
+    InvalidPublicKey,
+    /// RSA_PAD only works with 2048-bit keys
+    UnsupportedKeySize(usize),
+    /// SHA-1 inside the decrypted Server_DH_inner_data does not match
+    DhAnswerHashMismatch,
+    /// dh_prime is not a 2048-bit safe prime
+    InvalidDhPrime,
+    /// g is outside of 2..=7 or does not generate the expected subgroup for dh_prime
+    InvalidDhGenerator(u32),
+    /// g_a or g_b is too close to 1 or to dh_prime - 1
+    DhValueOutOfRange,
+    /// new_nonce_hash in dh_gen_ok/retry/fail does not match
+    NewNonceHashMismatch,
+    /// TL object is truncated or has an invalid length prefix
+    MalformedTl,
+    /// Server answered with a TL constructor that is not valid at this point
+    UnexpectedConstructor(u32),
+    /// nonce or server_nonce in a server answer does not match ours
+    NonceMismatch,
+    /// None of the fingerprints offered in resPQ has a registered public key
+    NoKnownFingerprint,
+    /// pq is not the product of two factors we could find
+    FactorizationFailed,
+    /// Server answered req_DH_params with server_DH_params_fail
+    ServerDhParamsFail,
+    /// Server answered set_client_DH_params with dh_gen_fail
+    DhGenFail,
+    /// Handshake message fed before start or after completion
+    InvalidHandshakeState,
+    /// Factorization used up its `max_iterations`, the seed replays the run
+    IterationLimit { iterations: u64, seed: u64 },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InvalidKeyLength { expected, .. } => write!(f, "Key must be {} bytes", expected),
+            Error::InvalidIvLength { expected, .. } => write!(f, "IV must be {} bytes", expected),
+            Error::InvalidDataLength => write!(f, "Data length must be a multiple of 16"),
+            Error::InvalidStateLength(_) => write!(f, "State size must be exactly 1 byte"),
+            Error::InvalidState(state) => {
+                write!(f, "State value must be in the range [0, 15], got {}", state)
+            }
+            Error::UnknownFingerprint(fingerprint) => {
+                write!(f, "Unknown fingerprint: {}", fingerprint)
+            }
+            Error::InvalidAuthKeyLength(_) => write!(f, "Auth key must be 256 bytes"),
+            Error::InvalidDirection(x) => write!(f, "x must be 0 or 8, got {}", x),
+            Error::InvalidPacketLength(len) => write!(f, "Invalid encrypted packet length: {}", len),
+            Error::AuthKeyIdMismatch => write!(f, "auth_key_id mismatch"),
+            Error::MsgKeyMismatch => write!(f, "msg_key mismatch"),
+            Error::BadPadding(len) => write!(f, "Invalid padding length: {}", len),
+            Error::BadMessageLength(len) => write!(f, "Invalid message_data_length: {}", len),
+            Error::SessionMismatch => write!(f, "session_id mismatch"),
+            Error::BadMsgId(msg_id) => write!(f, "Invalid msg_id: {}", msg_id),
+            Error::InvalidProtocolTag => write!(f, "Unknown protocol tag"),
+            Error::InvalidSecretLength(len) => write!(f, "Invalid MTProxy secret length: {}", len),
+            Error::InvalidInitPacket => write!(f, "Invalid obfuscated2 init packet"),
+            Error::InvalidFakeTlsSecret => write!(f, "Fake-TLS secret must be 0xee + 16-byte key + domain"),
+            Error::BadServerHello => write!(f, "Malformed ServerHello"),
+            Error::ServerHelloMismatch => write!(f, "ServerHello HMAC mismatch"),
+            Error::BadTlsRecord => write!(f, "Unexpected TLS record"),
+            Error::UnknownSecret => write!(f, "No MTProxy secret matches"),
+            Error::BadClientHello => write!(f, "Malformed ClientHello"),
+            Error::ClientHelloExpired(timestamp) => {
+                write!(f, "ClientHello timestamp out of range: {}", timestamp)
+            }
+            Error::InitPacketPending => write!(f, "Init packet not received yet"),
+            Error::PacketTooLarge(len) => write!(f, "Packet too large: {}", len),
+            Error::UnalignedPacket(len) => write!(f, "Packet length must be a multiple of 4, got {}", len),
+            Error::BadCrc => write!(f, "CRC32 mismatch"),
+            Error::BadSeqNo(seq_no) => write!(f, "Unexpected transport seq_no: {}", seq_no),
+            Error::DataTooLong { max, actual } => {
+                write!(f, "Data must be at most {} bytes, got {}", max, actual)
+            }
+            Error::DataExceedsModulus => write!(f, "Data must be smaller than the RSA modulus"),
+            Error::InvalidPublicKey => write!(f, "Invalid RSA public key"),
+            Error::UnsupportedKeySize(bits) => write!(f, "RSA key must be 2048 bits, got {}", bits),
+            Error::DhAnswerHashMismatch => write!(f, "Server_DH_inner_data hash mismatch"),
+            Error::InvalidDhPrime => write!(f, "dh_prime must be a 2048-bit safe prime"),
+            Error::InvalidDhGenerator(g) => write!(f, "Invalid DH generator: {}", g),
+            Error::DhValueOutOfRange => write!(f, "DH value out of the safe range"),
+            Error::NewNonceHashMismatch => write!(f, "new_nonce_hash mismatch"),
+            Error::MalformedTl => write!(f, "Malformed TL object"),
+            Error::UnexpectedConstructor(constructor) => {
+                write!(f, "Unexpected TL constructor: {:#010x}", constructor)
+            }
+            Error::NonceMismatch => write!(f, "Handshake nonce mismatch"),
+            Error::NoKnownFingerprint => write!(f, "No known RSA key among the server fingerprints"),
+            Error::FactorizationFailed => write!(f, "Could not factorize pq"),
+            Error::ServerDhParamsFail => write!(f, "Server answered server_DH_params_fail"),
+            Error::DhGenFail => write!(f, "Server answered dh_gen_fail"),
+            Error::InvalidHandshakeState => write!(f, "Handshake is not waiting for a server message"),
+            Error::IterationLimit { iterations, seed } => {
+                write!(f, "Factorization stopped after {} iterations (seed {})", iterations, seed)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
@@ -1,76 +1,552 @@
 use num_bigint::BigUint;
 use num_integer::Integer;
-use num_traits::{One, ToPrimitive, Zero};
-use pyo3::prelude::*;
-use rand::RngExt;
+use num_traits::ops::overflowing::{OverflowingAdd, OverflowingSub};
+use num_traits::{One, PrimInt, ToPrimitive, WrappingAdd, WrappingMul, WrappingSub, Zero};
 
-/// Pollard's rho algorithm for integer factorization
-/// Returns a non-trivial factor of pq
-fn pollard_rho(n: &BigUint) -> BigUint {
-    let two = BigUint::from(2u32);
-    let one = BigUint::one();
+use crate::error::{Error, Result};
 
+/// Steps of Brent's cycle-finding between two gcds
+const GCD_BATCH: usize = 128;
+
+/// (x0, c) pairs tried before giving up, a cycle closing without a factor is rare for composites
+const MAX_ATTEMPTS: usize = 10;
+
+/// Unsigned machine word usable for Montgomery arithmetic
+trait Word: PrimInt + WrappingAdd + WrappingSub + WrappingMul + OverflowingAdd + OverflowingSub {
+    /// Full double-width product as (low, high)
+    fn widening_mul(self, rhs: Self) -> (Self, Self);
+
+    /// Truncating conversion, used to draw random values
+    fn from_u128(value: u128) -> Self;
+}
+
+impl Word for u64 {
+    fn widening_mul(self, rhs: Self) -> (Self, Self) {
+        let product = self as u128 * rhs as u128;
+        (product as u64, (product >> 64) as u64)
+    }
+
+    fn from_u128(value: u128) -> Self {
+        value as u64
+    }
+}
+
+impl Word for u128 {
+    fn widening_mul(self, rhs: Self) -> (Self, Self) {
+        // Schoolbook multiplication on 64-bit halves
+        let (a0, a1) = (self as u64 as u128, self >> 64);
+        let (b0, b1) = (rhs as u64 as u128, rhs >> 64);
+        let (p00, p01, p10, p11) = (a0 * b0, a0 * b1, a1 * b0, a1 * b1);
+
+        let mid = (p00 >> 64) + (p01 as u64 as u128) + (p10 as u64 as u128);
+        let low = (p00 as u64 as u128) | (mid << 64);
+        let high = p11 + (p01 >> 64) + (p10 >> 64) + (mid >> 64);
+        (low, high)
+    }
+
+    fn from_u128(value: u128) -> Self {
+        value
+    }
+}
+
+/// Montgomery multiplication modulo an odd n, with R = 2^bits of the word
+/// Values stay in [0, n) and are never converted back: gcd(x * R, n) = gcd(x, n) as R is coprime to n
+struct Montgomery<T> {
+    n: T,
+    /// n^-1 mod R
+    inv: T,
+}
+
+impl<T: Word> Montgomery<T> {
+    fn new(n: T) -> Self {
+        // Newton's iteration doubles the number of correct low bits, odd n is its own inverse mod 8
+        let two = T::one() + T::one();
+        let mut inv = n;
+        for _ in 0..6 {
+            inv = inv.wrapping_mul(&two.wrapping_sub(&n.wrapping_mul(&inv)));
+        }
+        Self { n, inv }
+    }
+
+    /// a * b / R mod n
+    fn mul(&self, a: T, b: T) -> T {
+        let (low, high) = a.widening_mul(b);
+        // low - m * n = 0 mod R, so a * b - m * n = (high - high(m * n)) * R
+        let (_, mn_high) = low.wrapping_mul(&self.inv).widening_mul(self.n);
+        let (result, borrow) = high.overflowing_sub(&mn_high);
+        if borrow {
+            result.wrapping_add(&self.n)
+        } else {
+            result
+        }
+    }
+
+    /// a + b mod n
+    fn add(&self, a: T, b: T) -> T {
+        let (sum, carry) = a.overflowing_add(&b);
+        if carry || sum >= self.n {
+            sum.wrapping_sub(&self.n)
+        } else {
+            sum
+        }
+    }
+}
+
+/// Binary GCD
+fn gcd<T: Word>(mut a: T, mut b: T) -> T {
+    if a.is_zero() {
+        return b;
+    }
+    if b.is_zero() {
+        return a;
+    }
+    let shift = (a | b).trailing_zeros() as usize;
+    a = a >> a.trailing_zeros() as usize;
+    loop {
+        b = b >> b.trailing_zeros() as usize;
+        if a > b {
+            std::mem::swap(&mut a, &mut b);
+        }
+        b = b - a;
+        if b.is_zero() {
+            return a << shift;
+        }
+    }
+}
+
+/// Arithmetic modulo n used by Brent's loop
+trait Ring {
+    type Value: Clone + PartialEq;
+
+    fn n(&self) -> Self::Value;
+    fn one(&self) -> Self::Value;
+    /// Uniform-ish value in [0, n)
+    fn random(&self, rng: &mut SplitMix64) -> Self::Value;
+    /// x^2 + c
+    fn step(&self, x: &Self::Value, c: &Self::Value) -> Self::Value;
+    /// q * |x - y|
+    fn mul_diff(&self, q: &Self::Value, x: &Self::Value, y: &Self::Value) -> Self::Value;
+    /// gcd(q, n)
+    fn gcd(&self, q: &Self::Value) -> Self::Value;
+}
+
+impl<T: Word> Ring for Montgomery<T> {
+    type Value = T;
+
+    fn n(&self) -> T {
+        self.n
+    }
+
+    fn one(&self) -> T {
+        T::one()
+    }
+
+    fn random(&self, rng: &mut SplitMix64) -> T {
+        let value = (rng.next() as u128) << 64 | rng.next() as u128;
+        T::from_u128(value % self.n.to_u128().unwrap())
+    }
+
+    fn step(&self, x: &T, c: &T) -> T {
+        self.add(self.mul(*x, *x), *c)
+    }
+
+    fn mul_diff(&self, q: &T, x: &T, y: &T) -> T {
+        self.mul(*q, if x > y { *x - *y } else { *y - *x })
+    }
+
+    fn gcd(&self, q: &T) -> T {
+        gcd(*q, self.n)
+    }
+}
+
+/// Plain BigUint arithmetic for values above 128 bits
+struct BigRing {
+    n: BigUint,
+}
+
+impl Ring for BigRing {
+    type Value = BigUint;
+
+    fn n(&self) -> BigUint {
+        self.n.clone()
+    }
+
+    fn one(&self) -> BigUint {
+        BigUint::one()
+    }
+
+    fn random(&self, rng: &mut SplitMix64) -> BigUint {
+        // 64 extra bits keep the modulo bias negligible
+        let bytes: Vec<u8> = (0..self.n.bits().div_ceil(64) + 1).flat_map(|_| rng.next().to_le_bytes()).collect();
+        BigUint::from_bytes_le(&bytes) % &self.n
+    }
+
+    fn step(&self, x: &BigUint, c: &BigUint) -> BigUint {
+        (x * x + c) % &self.n
+    }
+
+    fn mul_diff(&self, q: &BigUint, x: &BigUint, y: &BigUint) -> BigUint {
+        let diff = if x > y { x - y } else { y - x };
+        q * diff % &self.n
+    }
+
+    fn gcd(&self, q: &BigUint) -> BigUint {
+        q.gcd(&self.n)
+    }
+}
+
+/// SplitMix64, so that a seed reproduces the same run on every platform and release
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+}
+
+/// Count of x -> x^2 + c evaluations, with an optional limit
+/// `seed` is only carried along so that `IterationLimit` can report it
+struct Budget {
+    used: u64,
+    max: Option<u64>,
+    seed: u64,
+}
+
+impl Budget {
+    fn step(&mut self) -> Result<()> {
+        if self.max == Some(self.used) {
+            return Err(Error::IterationLimit { iterations: self.used, seed: self.seed });
+        }
+        self.used += 1;
+        Ok(())
+    }
+}
+
+/// Brent's variant of Pollard's rho on x -> x^2 + c
+/// The differences are multiplied together and only their product goes through a gcd, every GCD_BATCH steps.
+/// Returns None when the cycle closes without revealing a factor
+fn brent<R: Ring>(ring: &R, x0: &R::Value, c: &R::Value, budget: &mut Budget) -> Result<Option<R::Value>> {
+    let (n, one) = (ring.n(), ring.one());
+    let (mut x, mut y, mut ys) = (x0.clone(), x0.clone(), x0.clone());
+    let (mut q, mut d) = (one.clone(), one.clone());
+    let mut r = 1usize;
+    while d == one {
+        x = y.clone();
+        for _ in 0..r {
+            budget.step()?;
+            y = ring.step(&y, c);
+        }
+        let mut k = 0;
+        while k < r && d == one {
+            ys = y.clone();
+            for _ in 0..GCD_BATCH.min(r - k) {
+                budget.step()?;
+                y = ring.step(&y, c);
+                q = ring.mul_diff(&q, &x, &y);
+            }
+            d = ring.gcd(&q);
+            k += GCD_BATCH;
+        }
+        r *= 2;
+    }
+
+    // The batch overshot: replay it one gcd at a time
+    if d == n {
+        loop {
+            budget.step()?;
+            ys = ring.step(&ys, c);
+            d = ring.gcd(&ring.mul_diff(&one, &x, &ys));
+            if d != one {
+                break;
+            }
+        }
+    }
+    Ok((d != n).then_some(d))
+}
+
+/// Run Brent from fresh random (x0, c) until a factor shows up, at most MAX_ATTEMPTS times
+fn find_factor<R: Ring>(ring: &R, rng: &mut SplitMix64, budget: &mut Budget) -> Result<R::Value> {
+    for _ in 0..MAX_ATTEMPTS {
+        let x0 = ring.random(rng);
+        let c = ring.random(rng);
+        if let Some(d) = brent(ring, &x0, &c, budget)? {
+            return Ok(d);
+        }
+    }
+    Err(Error::FactorizationFailed)
+}
+
+/// Non-trivial factor of n with Pollard's rho, failing instead of returning n itself
+/// n must not be prime: rho would only stop once every attempt's cycle closes.
+/// Values that fit in 64 or 128 bits use Montgomery multiplication, larger ones BigUint arithmetic
+fn split(n: &BigUint, rng: &mut SplitMix64, budget: &mut Budget) -> Result<BigUint> {
+    if *n < BigUint::from(4u32) {
+        return Err(Error::FactorizationFailed);
+    }
     if n.is_even() {
-        return two;
+        return Ok(BigUint::from(2u32));
     }
+    if let Some(n) = n.to_u64() {
+        return find_factor(&Montgomery::new(n), rng, budget).map(BigUint::from);
+    }
+    if let Some(n) = n.to_u128() {
+        return find_factor(&Montgomery::new(n), rng, budget).map(BigUint::from);
+    }
+    find_factor(&BigRing { n: n.clone() }, rng, budget)
+}
 
-    let mut rng = rand::rng();
+/// Result of `factorize_with`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Factorization {
+    /// Smaller factor
+    pub p: BigUint,
+    /// Larger factor
+    pub q: BigUint,
+    /// Evaluations of x -> x^2 + c, over every attempt
+    pub iterations: u64,
+    /// Seed that reproduces this run
+    pub seed: u64,
+}
 
-    // Try different starting values and constants if needed
-    for _ in 0..10 {
-        let x_start: u32 = rng.random_range(2..1000);
-        let c: u32 = rng.random_range(1..100);
+/// Split pq into its two factors, returned as (p, q) with p <= q
+/// Fails instead of returning pq when no non-trivial factor is found, e.g. for primes and values below 4
+pub fn factorize(pq: &BigUint) -> Result<(BigUint, BigUint)> {
+    factorize_with(pq, None, None).map(|f| (f.p, f.q))
+}
 
-        let mut x = BigUint::from(x_start);
-        let mut y = x.clone();
-        let c_big = BigUint::from(c);
-        let mut d = BigUint::one();
+/// `factorize` with a fixed seed for reproducible runs and a bound on the iterations
+/// A random seed is drawn when `seed` is None; exceeding `max_iterations` fails with `IterationLimit`
+pub fn factorize_with(pq: &BigUint, seed: Option<u64>, max_iterations: Option<u64>) -> Result<Factorization> {
+    let seed = seed.unwrap_or_else(rand::random);
+    let mut rng = SplitMix64(seed);
+    let mut budget = Budget { used: 0, max: max_iterations, seed };
 
-        while d.is_one() {
-            // x = (x^2 + c) mod n
-            x = (&x * &x + &c_big) % n;
+    if is_prime(pq) {
+        return Err(Error::FactorizationFailed);
+    }
+    let p = split(pq, &mut rng, &mut budget)?;
+    let q = pq / &p;
+    let (p, q) = if p <= q { (p, q) } else { (q, p) };
+    Ok(Factorization { p, q, iterations: budget.used, seed })
+}
 
-            // y = (y^2 + c) mod n, twice (Floyd's cycle-finding)
-            y = (&y * &y + &c_big) % n;
-            y = (&y * &y + &c_big) % n;
+/// Miller-Rabin bases that are deterministic for every n < 3.3 * 10^24, so for all of u64
+const MILLER_RABIN_BASES: [u64; 12] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37];
 
-            // d = gcd(|x - y|, n)
-            let diff = if x > y { &x - &y } else { &y - &x };
-            d = diff.gcd(n);
+/// Deterministic primality test
+/// Miller-Rabin with fixed bases below 2^64 and Baillie-PSW above, which has no known counterexample
+pub fn is_prime(n: &BigUint) -> bool {
+    if let Some(n) = n.to_u64() {
+        return is_prime_u64(n);
+    }
+    for &p in &MILLER_RABIN_BASES {
+        if (n % p).is_zero() {
+            return false;
         }
+    }
+    is_strong_probable_prime(n, &BigUint::from(2u32)) && is_strong_lucas_probable_prime(n)
+}
 
-        if d != *n && d != one {
-            return d;
+fn is_prime_u64(n: u64) -> bool {
+    for &p in &MILLER_RABIN_BASES {
+        if n.is_multiple_of(p) {
+            return n == p;
         }
     }
+    if n < 2 {
+        return false;
+    }
+
+    let mul = |a: u64, b: u64| (a as u128 * b as u128 % n as u128) as u64;
+    let pow = |mut base: u64, mut exp: u64| {
+        let mut result = 1;
+        while exp > 0 {
+            if exp & 1 == 1 {
+                result = mul(result, base);
+            }
+            base = mul(base, base);
+            exp >>= 1;
+        }
+        result
+    };
 
-    // Fallback: simple trial division for small factors
-    for i in 3..10000u32 {
-        let i_big = BigUint::from(i);
-        if (n % &i_big).is_zero() {
-            return i_big;
+    let s = (n - 1).trailing_zeros();
+    let d = (n - 1) >> s;
+    MILLER_RABIN_BASES.iter().all(|&a| {
+        let mut x = pow(a, d);
+        if x == 1 || x == n - 1 {
+            return true;
         }
+        (1..s).any(|_| {
+            x = mul(x, x);
+            x == n - 1
+        })
+    })
+}
+
+/// Strong Fermat test of odd n > 2 to base a
+fn is_strong_probable_prime(n: &BigUint, a: &BigUint) -> bool {
+    let n_minus_one = n - 1u32;
+    let s = n_minus_one.trailing_zeros().unwrap_or(0);
+    let d = &n_minus_one >> s;
+
+    let mut x = a.modpow(&d, n);
+    if x.is_one() || x == n_minus_one {
+        return true;
     }
+    for _ in 1..s {
+        x = x.modpow(&BigUint::from(2u32), n);
+        if x == n_minus_one {
+            return true;
+        }
+    }
+    false
+}
 
-    // Last resort: return n itself (shouldn't happen for valid PQ)
-    n.clone()
+/// Jacobi symbol (a / n) for odd n
+fn jacobi(a: &BigUint, n: &BigUint) -> i32 {
+    let (mut a, mut n) = (a % n, n.clone());
+    let mut result = 1;
+    while !a.is_zero() {
+        let twos = a.trailing_zeros().unwrap_or(0);
+        a >>= twos;
+        let n_mod_8 = (&n % 8u32).to_u32().unwrap();
+        if twos % 2 == 1 && (n_mod_8 == 3 || n_mod_8 == 5) {
+            result = -result;
+        }
+        if (&a % 4u32).to_u32() == Some(3) && n_mod_8 % 4 == 3 {
+            result = -result;
+        }
+        std::mem::swap(&mut a, &mut n);
+        a %= &n;
+    }
+    if n.is_one() {
+        result
+    } else {
+        0
+    }
 }
 
-/// Find a non-trivial factor using Pollard's rho algorithm
-#[pyfunction]
-#[pyo3(signature = (pq, /))]
-pub fn factorize(pq: i128) -> PyResult<i128> {
-    if pq <= 0 {
-        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
-            "pq must be positive",
-        ));
+/// Strong Lucas test of odd n with Selfridge's parameters: the first D in 5, -7, 9, -11, ...
+/// with (D / n) = -1, P = 1 and Q = (1 - D) / 4
+fn is_strong_lucas_probable_prime(n: &BigUint) -> bool {
+    // No such D exists for perfect squares
+    if n.sqrt().pow(2) == *n {
+        return false;
+    }
+
+    let mut d_abs = 5u32;
+    let mut negative = false;
+    let d = loop {
+        let d = match negative {
+            true => n - (BigUint::from(d_abs) % n),
+            false => BigUint::from(d_abs) % n,
+        };
+        match jacobi(&d, n) {
+            -1 => break d,
+            // D shares a factor with n, which is larger than D
+            0 => return false,
+            _ => {}
+        }
+        d_abs += 2;
+        negative = !negative;
+    };
+    // Q = (1 - D) / 4 mod n, with D = -d_abs or d_abs
+    let q = match negative {
+        true => BigUint::from((1 + d_abs) / 4) % n,
+        false => n - BigUint::from((d_abs - 1) / 4) % n,
+    } % n;
+
+    let sub = |a: &BigUint, b: &BigUint| (a + n - b % n) % n;
+    let half = |x: BigUint| if x.is_odd() { (x + n) >> 1 } else { x >> 1 };
+
+    // n + 1 = k * 2^s with k odd
+    let n_plus_one = n + 1u32;
+    let s = n_plus_one.trailing_zeros().unwrap_or(0);
+    let k = &n_plus_one >> s;
+
+    // U_1 = 1, V_1 = P = 1, Q^1
+    let (mut u, mut v, mut q_k) = (BigUint::one(), BigUint::one(), q.clone());
+    for bit in (0..k.bits() - 1).rev() {
+        u = &u * &v % n;
+        v = sub(&(&v * &v), &(&q_k << 1u32));
+        q_k = &q_k * &q_k % n;
+        if k.bit(bit) {
+            let (u_next, v_next) = (half(&u + &v), half(&d * &u + &v));
+            u = u_next % n;
+            v = v_next % n;
+            q_k = &q_k * &q % n;
+        }
     }
 
-    let pq_big = BigUint::from(pq as u128);
-    let factor = pollard_rho(&pq_big);
+    if u.is_zero() || v.is_zero() {
+        return true;
+    }
+    for _ in 1..s {
+        v = sub(&(&v * &v), &(&q_k << 1u32));
+        if v.is_zero() {
+            return true;
+        }
+        q_k = &q_k * &q_k % n;
+    }
+    false
+}
 
-    // Convert back to i128
-    let result = factor.to_i128().unwrap_or(pq);
+/// Trial division bound used by `factor_all` before Pollard's rho
+const TRIAL_DIVISION_BOUND: u32 = 1000;
+
+/// (root, k) with root^k = n for the smallest k > 1, if n is a perfect power
+/// n has no factor below `TRIAL_DIVISION_BOUND` > 2^9, which bounds k by bits / 9
+fn perfect_power(n: &BigUint) -> Option<(BigUint, u32)> {
+    (2..=(n.bits() / 9) as u32)
+        .map(|k| (n.nth_root(k), k))
+        .find(|(root, k)| root.pow(*k) == *n)
+}
+
+/// Full decomposition of n into (prime, exponent) pairs, in increasing order of the primes
+/// 1 gives an empty list, 0 fails with `FactorizationFailed`
+pub fn factor_all(n: &BigUint) -> Result<Vec<(BigUint, u32)>> {
+    factor_all_with(n, None, None)
+}
 
-    Ok(result)
+/// `factor_all` with a fixed seed and a bound on the iterations, shared by every rho split
+/// A random seed is drawn when `seed` is None; exceeding `max_iterations` fails with `IterationLimit`
+pub fn factor_all_with(n: &BigUint, seed: Option<u64>, max_iterations: Option<u64>) -> Result<Vec<(BigUint, u32)>> {
+    if n.is_zero() {
+        return Err(Error::FactorizationFailed);
+    }
+
+    let mut factors = std::collections::BTreeMap::new();
+    let mut n = n.clone();
+    // Composite divisors never divide what is left of n
+    for d in std::iter::once(2).chain((3..TRIAL_DIVISION_BOUND).step_by(2)) {
+        while (&n % d).is_zero() {
+            n /= d;
+            *factors.entry(BigUint::from(d)).or_insert(0) += 1;
+        }
+    }
+
+    let seed = seed.unwrap_or_else(rand::random);
+    let mut rng = SplitMix64(seed);
+    let mut budget = Budget { used: 0, max: max_iterations, seed };
+    let mut pending = vec![(n, 1)];
+    while let Some((m, exponent)) = pending.pop() {
+        if m.is_one() {
+            continue;
+        }
+        if is_prime(&m) {
+            *factors.entry(m).or_insert(0) += exponent;
+        } else if let Some((root, k)) = perfect_power(&m) {
+            pending.push((root, exponent * k));
+        } else {
+            let d = split(&m, &mut rng, &mut budget)?;
+            pending.push((&m / &d, exponent));
+            pending.push((d, exponent));
+        }
+    }
+    Ok(factors.into_iter().collect())
 }
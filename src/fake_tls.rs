@@ -0,0 +1,456 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use crate::error::{Error, Result};
+use crate::mtproto::ct_eq;
+use crate::obfuscated::{proxy_secret, Obfuscated2, Obfuscated2Server};
+
+/// Size of the ClientHello record, matching the one sent by browsers
+const CLIENT_HELLO_LEN: usize = 517;
+/// Largest payload put in a single application data record
+const MAX_RECORD_PAYLOAD: usize = 16384;
+
+const CHANGE_CIPHER_SPEC: [u8; 6] = [0x14, 0x03, 0x03, 0x00, 0x01, 0x01];
+const APPLICATION_DATA: [u8; 3] = [0x17, 0x03, 0x03];
+const HANDSHAKE: [u8; 3] = [0x16, 0x03, 0x03];
+
+/// Offset of the 32-byte random field in ClientHello and ServerHello records
+const RANDOM_OFFSET: usize = 11;
+
+pub(crate) fn hmac_sha256(key: &[u8], parts: &[&[u8]]) -> [u8; 32] {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).unwrap();
+    for part in parts {
+        mac.update(part);
+    }
+    mac.finalize().into_bytes().into()
+}
+
+pub(crate) fn unix_time() -> u32 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs() as u32)
+}
+
+/// MTProxy `ee` secret: 0xee + 16-byte key + domain name
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FakeTlsSecret {
+    pub key: [u8; 16],
+    pub domain: Vec<u8>,
+}
+
+impl FakeTlsSecret {
+    pub fn parse(secret: &[u8]) -> Result<Self> {
+        if secret.len() < 18 || secret[0] != 0xee {
+            return Err(Error::InvalidFakeTlsSecret);
+        }
+        Ok(Self {
+            key: secret[1..17].try_into().unwrap(),
+            domain: secret[17..].to_vec(),
+        })
+    }
+}
+
+/// Random GREASE value (RFC 8701) as a big-endian u16
+fn grease(seed: u8) -> [u8; 2] {
+    let value = (seed & 0xf0) | 0x0a;
+    [value, value]
+}
+
+fn push_u16(out: &mut Vec<u8>, value: usize) {
+    out.extend_from_slice(&(value as u16).to_be_bytes());
+}
+
+/// Build a TLS 1.3 ClientHello for `domain` with the random field zeroed
+fn build_client_hello(domain: &[u8]) -> Vec<u8> {
+    let mut random = [0u8; 72];
+    rand::fill(&mut random[..]);
+    let (session_id, key_share, greases) = (&random[..32], &random[32..64], &random[64..]);
+
+    let mut ext = Vec::with_capacity(CLIENT_HELLO_LEN);
+    ext.extend_from_slice(&grease(greases[0]));
+    ext.extend_from_slice(&[0x00, 0x00]);
+    // server_name
+    ext.extend_from_slice(&[0x00, 0x00]);
+    push_u16(&mut ext, domain.len() + 5);
+    push_u16(&mut ext, domain.len() + 3);
+    ext.push(0x00);
+    push_u16(&mut ext, domain.len());
+    ext.extend_from_slice(domain);
+    // extended_master_secret, renegotiation_info
+    ext.extend_from_slice(&[0x00, 0x17, 0x00, 0x00, 0xff, 0x01, 0x00, 0x01, 0x00]);
+    // supported_groups
+    ext.extend_from_slice(&[0x00, 0x0a, 0x00, 0x0a, 0x00, 0x08]);
+    ext.extend_from_slice(&grease(greases[1]));
+    ext.extend_from_slice(&[0x00, 0x1d, 0x00, 0x17, 0x00, 0x18]);
+    // ec_point_formats, session_ticket
+    ext.extend_from_slice(&[0x00, 0x0b, 0x00, 0x02, 0x01, 0x00, 0x00, 0x23, 0x00, 0x00]);
+    // application_layer_protocol_negotiation: h2, http/1.1
+    ext.extend_from_slice(&[0x00, 0x10, 0x00, 0x0e, 0x00, 0x0c, 0x02]);
+    ext.extend_from_slice(b"h2\x08http/1.1");
+    // status_request
+    ext.extend_from_slice(&[0x00, 0x05, 0x00, 0x05, 0x01, 0x00, 0x00, 0x00, 0x00]);
+    // signature_algorithms
+    ext.extend_from_slice(&[0x00, 0x0d, 0x00, 0x12, 0x00, 0x10]);
+    ext.extend_from_slice(&[0x04, 0x03, 0x08, 0x04, 0x04, 0x01, 0x05, 0x03, 0x08, 0x05, 0x05, 0x01, 0x08, 0x06, 0x06, 0x01]);
+    // signed_certificate_timestamp
+    ext.extend_from_slice(&[0x00, 0x12, 0x00, 0x00]);
+    // key_share: GREASE + x25519
+    ext.extend_from_slice(&[0x00, 0x33, 0x00, 0x2b, 0x00, 0x29]);
+    ext.extend_from_slice(&grease(greases[1]));
+    ext.extend_from_slice(&[0x00, 0x01, 0x00, 0x00, 0x1d, 0x00, 0x20]);
+    ext.extend_from_slice(key_share);
+    // psk_key_exchange_modes
+    ext.extend_from_slice(&[0x00, 0x2d, 0x00, 0x02, 0x01, 0x01]);
+    // supported_versions: GREASE, TLS 1.3, 1.2, 1.1, 1.0
+    ext.extend_from_slice(&[0x00, 0x2b, 0x00, 0x0b, 0x0a]);
+    ext.extend_from_slice(&grease(greases[2]));
+    ext.extend_from_slice(&[0x03, 0x04, 0x03, 0x03, 0x03, 0x02, 0x03, 0x01]);
+    // compress_certificate
+    ext.extend_from_slice(&[0x00, 0x1b, 0x00, 0x03, 0x02, 0x00, 0x02]);
+    ext.extend_from_slice(&grease(greases[3]));
+    ext.extend_from_slice(&[0x00, 0x01, 0x00]);
+
+    let mut body = Vec::with_capacity(CLIENT_HELLO_LEN);
+    body.extend_from_slice(&[0x03, 0x03]);
+    body.extend_from_slice(&[0u8; 32]);
+    body.push(0x20);
+    body.extend_from_slice(session_id);
+    body.extend_from_slice(&[0x00, 0x20]);
+    body.extend_from_slice(&grease(greases[4]));
+    body.extend_from_slice(&[
+        0x13, 0x01, 0x13, 0x02, 0x13, 0x03, 0xc0, 0x2b, 0xc0, 0x2f, 0xc0, 0x2c, 0xc0, 0x30, 0xcc, 0xa9,
+        0xcc, 0xa8, 0xc0, 0x13, 0xc0, 0x14, 0x00, 0x9c, 0x00, 0x9d, 0x00, 0x2f, 0x00, 0x35,
+    ]);
+    body.extend_from_slice(&[0x01, 0x00]);
+
+    // Record (5) + handshake header (4) + body + extensions length (2) + padding extension (4)
+    let unpadded = 5 + 4 + body.len() + 2 + ext.len() + 4;
+    let padding = CLIENT_HELLO_LEN.saturating_sub(unpadded);
+    ext.extend_from_slice(&[0x00, 0x15]);
+    push_u16(&mut ext, padding);
+    ext.resize(ext.len() + padding, 0);
+
+    push_u16(&mut body, ext.len());
+    body.extend_from_slice(&ext);
+
+    let mut hello = Vec::with_capacity(CLIENT_HELLO_LEN);
+    hello.extend_from_slice(&[0x16, 0x03, 0x01]);
+    push_u16(&mut hello, body.len() + 4);
+    hello.extend_from_slice(&[0x01, 0x00]);
+    push_u16(&mut hello, body.len());
+    hello.extend_from_slice(&body);
+    hello
+}
+
+/// Build the ServerHello + ChangeCipherSpec + application data reply to `client_hello`
+/// The server random is HMAC-SHA256(key, client random + reply with zeroed random)
+fn build_server_hello(key: &[u8; 16], client_hello: &[u8]) -> Vec<u8> {
+    let mut random = [0u8; 34];
+    rand::fill(&mut random[..]);
+    let (key_share, size) = (&random[..32], &random[32..]);
+    let payload_len = 1024 + u16::from_le_bytes([size[0], size[1]]) as usize % 3072;
+
+    let mut response = Vec::with_capacity(127 + CHANGE_CIPHER_SPEC.len() + 5 + payload_len);
+    response.extend_from_slice(&HANDSHAKE);
+    response.extend_from_slice(&[0x00, 0x7a, 0x02, 0x00, 0x00, 0x76, 0x03, 0x03]);
+    response.extend_from_slice(&[0u8; 32]);
+    // Echo the client's session id, then TLS_AES_128_GCM_SHA256 without compression
+    response.push(0x20);
+    response.extend_from_slice(&client_hello[RANDOM_OFFSET + 33..RANDOM_OFFSET + 65]);
+    response.extend_from_slice(&[0x13, 0x01, 0x00, 0x00, 0x2e]);
+    // key_share: x25519
+    response.extend_from_slice(&[0x00, 0x33, 0x00, 0x24, 0x00, 0x1d, 0x00, 0x20]);
+    response.extend_from_slice(key_share);
+    // supported_versions: TLS 1.3
+    response.extend_from_slice(&[0x00, 0x2b, 0x00, 0x02, 0x03, 0x04]);
+
+    response.extend_from_slice(&CHANGE_CIPHER_SPEC);
+    response.extend_from_slice(&APPLICATION_DATA);
+    push_u16(&mut response, payload_len);
+    let start = response.len();
+    response.resize(start + payload_len, 0);
+    rand::fill(&mut response[start..]);
+
+    let digest = hmac_sha256(key, &[&client_hello[RANDOM_OFFSET..RANDOM_OFFSET + 32], &response]);
+    response[RANDOM_OFFSET..RANDOM_OFFSET + 32].copy_from_slice(&digest);
+    response
+}
+
+/// Length of the complete TLS record starting at `data`, if it has been fully received
+fn record_len(data: &[u8]) -> Option<usize> {
+    if data.len() < 5 {
+        return None;
+    }
+    let len = 5 + u16::from_be_bytes([data[3], data[4]]) as usize;
+    (data.len() >= len).then_some(len)
+}
+
+/// Wraps and unwraps TLS application data records
+#[derive(Debug, Clone, Default)]
+pub struct RecordLayer {
+    change_cipher_spec_sent: bool,
+    buffer: Vec<u8>,
+}
+
+impl RecordLayer {
+    /// Split `data` into application data records
+    /// The first call also emits the ChangeCipherSpec record
+    pub fn wrap(&mut self, data: &[u8]) -> Vec<u8> {
+        let mut out = Vec::with_capacity(data.len() + 6 + 5 * (data.len() / MAX_RECORD_PAYLOAD + 1));
+        if !self.change_cipher_spec_sent {
+            out.extend_from_slice(&CHANGE_CIPHER_SPEC);
+            self.change_cipher_spec_sent = true;
+        }
+        for chunk in data.chunks(MAX_RECORD_PAYLOAD) {
+            out.extend_from_slice(&APPLICATION_DATA);
+            push_u16(&mut out, chunk.len());
+            out.extend_from_slice(chunk);
+        }
+        out
+    }
+
+    /// Feed received bytes and return the payload of every complete record
+    pub fn feed(&mut self, data: &[u8]) -> Result<Vec<u8>> {
+        self.buffer.extend_from_slice(data);
+
+        let mut out = Vec::new();
+        let mut pos = 0;
+        while let Some(len) = record_len(&self.buffer[pos..]) {
+            let record = &self.buffer[pos..pos + len];
+            if record[..3] == APPLICATION_DATA {
+                out.extend_from_slice(&record[5..]);
+            } else if record[..] != CHANGE_CIPHER_SPEC {
+                return Err(Error::BadTlsRecord);
+            }
+            pos += len;
+        }
+        self.buffer.drain(..pos);
+        Ok(out)
+    }
+}
+
+/// Client side of the MTProxy fake-TLS transport
+/// Sends a ClientHello carrying an HMAC of the secret, checks the server's HMAC'd
+/// ServerHello and then carries obfuscated2 traffic in application data records
+#[derive(Clone)]
+pub struct FakeTlsClient {
+    key: [u8; 16],
+    hello: Vec<u8>,
+    obfuscated: Obfuscated2,
+    records: RecordLayer,
+    init_sent: bool,
+}
+
+impl FakeTlsClient {
+    pub fn new(secret: &[u8], tag: &[u8], dc_id: i16) -> Result<Self> {
+        Self::with_timestamp(secret, tag, dc_id, unix_time())
+    }
+
+    /// Build the ClientHello as if sent at `timestamp` (unix seconds)
+    pub fn with_timestamp(secret: &[u8], tag: &[u8], dc_id: i16, timestamp: u32) -> Result<Self> {
+        let secret = FakeTlsSecret::parse(secret)?;
+        let obfuscated = Obfuscated2::new(tag, dc_id, Some(&secret.key))?;
+
+        // random = HMAC-SHA256(key, hello) with the timestamp XOR'ed into the last 4 bytes
+        let mut hello = build_client_hello(&secret.domain);
+        let mut random = hmac_sha256(&secret.key, &[&hello]);
+        for (byte, t) in random[28..].iter_mut().zip(timestamp.to_le_bytes()) {
+            *byte ^= t;
+        }
+        hello[RANDOM_OFFSET..RANDOM_OFFSET + 32].copy_from_slice(&random);
+
+        Ok(Self {
+            key: secret.key,
+            hello,
+            obfuscated,
+            records: RecordLayer::default(),
+            init_sent: false,
+        })
+    }
+
+    /// The ClientHello record to send first
+    pub fn client_hello(&self) -> &[u8] {
+        &self.hello
+    }
+
+    /// Check the ServerHello, ChangeCipherSpec and application data records sent back
+    /// Returns the number of bytes they span, or None if more data is needed
+    pub fn verify_server_hello(&self, response: &[u8]) -> Result<Option<usize>> {
+        let mut total = 0;
+        for expected in [&HANDSHAKE[..], &CHANGE_CIPHER_SPEC[..3], &APPLICATION_DATA[..]] {
+            let rest = &response[total..];
+            if rest.len() >= 3 && rest[..3] != *expected {
+                return Err(Error::BadServerHello);
+            }
+            match record_len(rest) {
+                // The ServerHello record must be large enough to hold its random field
+                Some(len) if total == 0 && len < RANDOM_OFFSET + 32 => return Err(Error::BadServerHello),
+                Some(len) => total += len,
+                None => return Ok(None),
+            }
+        }
+
+        // The server random is HMAC-SHA256(key, client random + response with zeroed random)
+        let mut zeroed = response[..total].to_vec();
+        zeroed[RANDOM_OFFSET..RANDOM_OFFSET + 32].fill(0);
+        let digest = hmac_sha256(&self.key, &[&self.hello[RANDOM_OFFSET..RANDOM_OFFSET + 32], &zeroed]);
+        if !ct_eq(&digest, &response[RANDOM_OFFSET..RANDOM_OFFSET + 32]) {
+            return Err(Error::ServerHelloMismatch);
+        }
+
+        Ok(Some(total))
+    }
+
+    /// Encrypt outgoing data and wrap it in application data records
+    /// The first call also carries the obfuscated2 init header
+    pub fn encrypt(&mut self, data: &[u8]) -> Vec<u8> {
+        let mut payload = Vec::with_capacity(64 + data.len());
+        if !self.init_sent {
+            payload.extend_from_slice(self.obfuscated.init_packet());
+            self.init_sent = true;
+        }
+        let start = payload.len();
+        payload.extend_from_slice(data);
+        self.obfuscated.encryptor().process(&mut payload[start..]);
+        self.records.wrap(&payload)
+    }
+
+    /// Unwrap incoming records and decrypt their payload
+    pub fn decrypt(&mut self, data: &[u8]) -> Result<Vec<u8>> {
+        let mut payload = self.records.feed(data)?;
+        self.obfuscated.decryptor().process(&mut payload);
+        Ok(payload)
+    }
+}
+
+/// Server side of the MTProxy fake-TLS transport
+/// Matches the ClientHello HMAC against the configured secrets, replies with an HMAC'd
+/// ServerHello and then reads the obfuscated2 init header from the first records
+#[derive(Clone)]
+pub struct FakeTlsServer {
+    key: [u8; 16],
+    secret_index: usize,
+    timestamp: u32,
+    server_hello: Vec<u8>,
+    records: RecordLayer,
+    pending: Vec<u8>,
+    obfuscated: Option<Obfuscated2Server>,
+}
+
+impl FakeTlsServer {
+    /// Validate a ClientHello against `secrets`, allowing `max_skew` seconds of clock drift
+    /// Each secret is either an `ee` secret or a plain 16-byte key, malformed secrets never match
+    pub fn accept(client_hello: &[u8], secrets: &[&[u8]], max_skew: u32) -> Result<Self> {
+        Self::accept_at(client_hello, secrets, unix_time(), max_skew)
+    }
+
+    /// Validate a ClientHello as if received at `now` (unix seconds)
+    /// Bytes after the ClientHello record are kept for the record layer, the next `decrypt`
+    /// (even of no data) returns their payload
+    pub fn accept_at(client_hello: &[u8], secrets: &[&[u8]], now: u32, max_skew: u32) -> Result<Self> {
+        let len = record_len(client_hello).ok_or(Error::BadClientHello)?;
+        let (hello, rest) = client_hello.split_at(len);
+
+        // Record and handshake headers, version, random and a 32-byte session id
+        if hello.len() < RANDOM_OFFSET + 65
+            || hello[..3] != [0x16, 0x03, 0x01]
+            || hello[5] != 0x01
+            || hello[RANDOM_OFFSET + 32] != 0x20
+        {
+            return Err(Error::BadClientHello);
+        }
+
+        let random = &hello[RANDOM_OFFSET..RANDOM_OFFSET + 32];
+        let mut zeroed = hello.to_vec();
+        zeroed[RANDOM_OFFSET..RANDOM_OFFSET + 32].fill(0);
+
+        for (secret_index, secret) in secrets.iter().enumerate() {
+            let key = match FakeTlsSecret::parse(secret) {
+                Ok(secret) => secret.key,
+                Err(_) => match proxy_secret(secret) {
+                    Ok(key) => key,
+                    Err(_) => continue,
+                },
+            };
+
+            let digest = hmac_sha256(&key, &[&zeroed]);
+            if !ct_eq(&digest[..28], &random[..28]) {
+                continue;
+            }
+
+            let mut timestamp = [0u8; 4];
+            for (t, (a, b)) in timestamp.iter_mut().zip(digest[28..].iter().zip(&random[28..])) {
+                *t = a ^ b;
+            }
+            let timestamp = u32::from_le_bytes(timestamp);
+            if now.abs_diff(timestamp) > max_skew {
+                return Err(Error::ClientHelloExpired(timestamp));
+            }
+
+            return Ok(Self {
+                key,
+                secret_index,
+                timestamp,
+                server_hello: build_server_hello(&key, hello),
+                records: RecordLayer { change_cipher_spec_sent: true, buffer: rest.to_vec() },
+                pending: Vec::new(),
+                obfuscated: None,
+            });
+        }
+
+        Err(Error::UnknownSecret)
+    }
+
+    /// Index of the secret the client used
+    pub fn secret_index(&self) -> usize {
+        self.secret_index
+    }
+
+    /// Unix time the client put in its ClientHello
+    pub fn timestamp(&self) -> u32 {
+        self.timestamp
+    }
+
+    /// The ServerHello, ChangeCipherSpec and application data records to send back
+    pub fn server_hello(&self) -> &[u8] {
+        &self.server_hello
+    }
+
+    /// Protocol tag requested by the client, once the init header has been received
+    pub fn tag(&self) -> Option<[u8; 4]> {
+        self.obfuscated.as_ref().map(Obfuscated2Server::tag)
+    }
+
+    /// DC id requested by the client, once the init header has been received
+    pub fn dc_id(&self) -> Option<i16> {
+        self.obfuscated.as_ref().map(Obfuscated2Server::dc_id)
+    }
+
+    /// Encrypt outgoing data and wrap it in application data records
+    pub fn encrypt(&mut self, data: &[u8]) -> Result<Vec<u8>> {
+        let obfuscated = self.obfuscated.as_mut().ok_or(Error::InitPacketPending)?;
+        let mut payload = data.to_vec();
+        obfuscated.encryptor().process(&mut payload);
+        Ok(self.records.wrap(&payload))
+    }
+
+    /// Unwrap incoming records and decrypt their payload
+    /// The first 64 bytes are taken as the obfuscated2 init header
+    pub fn decrypt(&mut self, data: &[u8]) -> Result<Vec<u8>> {
+        let mut payload = self.records.feed(data)?;
+
+        if self.obfuscated.is_none() {
+            self.pending.extend_from_slice(&payload);
+            if self.pending.len() < 64 {
+                return Ok(Vec::new());
+            }
+            self.obfuscated = Some(Obfuscated2Server::accept(&self.pending[..64], &[&self.key])?);
+            payload = self.pending.split_off(64);
+            self.pending.clear();
+        }
+
+        let obfuscated = self.obfuscated.as_mut().unwrap();
+        obfuscated.decryptor().process(&mut payload);
+        Ok(payload)
+    }
+}
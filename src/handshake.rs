@@ -0,0 +1,338 @@
+use num_bigint::BigUint;
+
+use crate::dh::{self, DH_PRIME_LEN};
+use crate::error::{Error, Result};
+use crate::factorization;
+use crate::mtproto::{self, DhGenStatus};
+use crate::rsa;
+use crate::sha::sha1;
+use crate::tl::{self, Reader};
+
+const REQ_PQ_MULTI: u32 = 0xbe7e8ef1;
+const RES_PQ: u32 = 0x05162463;
+const P_Q_INNER_DATA_DC: u32 = 0xa9f55f95;
+const P_Q_INNER_DATA_TEMP_DC: u32 = 0x56fddf88;
+const REQ_DH_PARAMS: u32 = 0xd712e4be;
+const SERVER_DH_PARAMS_FAIL: u32 = 0x79cb045d;
+const SERVER_DH_PARAMS_OK: u32 = 0xd0e8075c;
+const SERVER_DH_INNER_DATA: u32 = 0xb5890dba;
+const CLIENT_DH_INNER_DATA: u32 = 0x6643b654;
+const SET_CLIENT_DH_PARAMS: u32 = 0xf5045f1f;
+const DH_GEN_OK: u32 = 0x3bcbf734;
+const DH_GEN_RETRY: u32 = 0x46dc1fb9;
+const DH_GEN_FAIL: u32 = 0xa69dae02;
+
+/// Kind of auth key to create
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyKind {
+    /// Permanent key, sent as p_q_inner_data_dc
+    Permanent,
+    /// Temporary key valid for `expires_in` seconds, sent as p_q_inner_data_temp_dc
+    Temporary { expires_in: i32 },
+}
+
+/// Result of a completed handshake
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuthKey {
+    pub auth_key: [u8; DH_PRIME_LEN],
+    pub auth_key_id: [u8; 8],
+    pub server_salt: i64,
+    /// Server time minus local time, in seconds
+    pub time_offset: i64,
+    /// Server time the temporary key expires at, None for permanent keys
+    pub expires_at: Option<i64>,
+}
+
+/// What to do after feeding a server message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Step {
+    /// Send this message to the server and feed the answer
+    Send(Vec<u8>),
+    /// The handshake is complete
+    Done(Box<AuthKey>),
+}
+
+/// Nonces shared by every message after resPQ
+struct Nonces {
+    nonce: [u8; 16],
+    server_nonce: [u8; 16],
+    new_nonce: [u8; 32],
+}
+
+/// Validated Server_DH_inner_data
+struct DhParams {
+    g: u32,
+    dh_prime: Vec<u8>,
+    g_a: Vec<u8>,
+}
+
+enum State {
+    Idle,
+    ResPq { nonce: [u8; 16] },
+    ServerDhParams { nonces: Nonces },
+    DhGen { nonces: Nonces, params: DhParams, auth_key: Box<[u8; DH_PRIME_LEN]> },
+}
+
+/// Sans-IO auth key creation
+/// `start` returns req_pq_multi, then each server answer passed to `feed` yields the next message
+/// to send, up to dh_gen_ok. Messages are plain (unencrypted) MTProto messages without transport framing.
+/// Any error resets the handshake, which can then be started again.
+pub struct AuthKeyHandshake {
+    dc_id: i32,
+    kind: KeyKind,
+    state: State,
+    last_msg_id: i64,
+    time_offset: i64,
+}
+
+impl AuthKeyHandshake {
+    /// `dc_id` goes into p_q_inner_data: +10000 for test servers, negative for media DCs
+    pub fn new(dc_id: i32, kind: KeyKind) -> Self {
+        Self {
+            dc_id,
+            kind,
+            state: State::Idle,
+            last_msg_id: 0,
+            time_offset: 0,
+        }
+    }
+
+    /// Whether a handshake is in progress
+    pub fn is_started(&self) -> bool {
+        !matches!(self.state, State::Idle)
+    }
+
+    /// Start (or restart) the handshake and return req_pq_multi
+    /// `now` is the current unix time in seconds, used for msg_id
+    pub fn start(&mut self, now: i64) -> Vec<u8> {
+        let mut nonce = [0u8; 16];
+        rand::fill(&mut nonce[..]);
+        self.state = State::ResPq { nonce };
+
+        let mut body = Vec::with_capacity(20);
+        body.extend_from_slice(&REQ_PQ_MULTI.to_le_bytes());
+        body.extend_from_slice(&nonce);
+        self.pack(&body, now)
+    }
+
+    /// Process a message from the server
+    pub fn feed(&mut self, packet: &[u8], now: i64) -> Result<Step> {
+        let state = std::mem::replace(&mut self.state, State::Idle);
+        let body = unpack(packet)?;
+        match state {
+            State::Idle => Err(Error::InvalidHandshakeState),
+            State::ResPq { nonce } => self.res_pq(body, nonce, now),
+            State::ServerDhParams { nonces } => self.server_dh_params(body, nonces, now),
+            State::DhGen { nonces, params, auth_key } => self.dh_gen(body, nonces, params, auth_key, now),
+        }
+    }
+
+    /// resPQ: pick a known RSA key, factorize pq and send req_DH_params
+    fn res_pq(&mut self, body: &[u8], nonce: [u8; 16], now: i64) -> Result<Step> {
+        let mut reader = Reader::new(body);
+        expect(&mut reader, RES_PQ)?;
+        check_nonce(&mut reader, &nonce)?;
+        let server_nonce = reader.array()?;
+        let pq = reader.bytes()?;
+        let fingerprint = reader
+            .vector_i64()?
+            .into_iter()
+            .find(|&fingerprint| rsa::get_public_key(fingerprint).is_some())
+            .ok_or(Error::NoKnownFingerprint)?;
+
+        let (p, q) = factorize_pq(pq)?;
+        let mut new_nonce = [0u8; 32];
+        rand::fill(&mut new_nonce[..]);
+
+        let mut inner = Vec::with_capacity(rsa::RSA_PAD_MAX_DATA);
+        match self.kind {
+            KeyKind::Permanent => inner.extend_from_slice(&P_Q_INNER_DATA_DC.to_le_bytes()),
+            KeyKind::Temporary { .. } => inner.extend_from_slice(&P_Q_INNER_DATA_TEMP_DC.to_le_bytes()),
+        }
+        tl::write_bytes(&mut inner, pq);
+        tl::write_bytes(&mut inner, &p);
+        tl::write_bytes(&mut inner, &q);
+        inner.extend_from_slice(&nonce);
+        inner.extend_from_slice(&server_nonce);
+        inner.extend_from_slice(&new_nonce);
+        inner.extend_from_slice(&self.dc_id.to_le_bytes());
+        if let KeyKind::Temporary { expires_in } = self.kind {
+            inner.extend_from_slice(&expires_in.to_le_bytes());
+        }
+        let encrypted_data = rsa::rsa_pad_encrypt(&inner, fingerprint)?;
+
+        let mut body = Vec::with_capacity(320 + encrypted_data.len());
+        body.extend_from_slice(&REQ_DH_PARAMS.to_le_bytes());
+        body.extend_from_slice(&nonce);
+        body.extend_from_slice(&server_nonce);
+        tl::write_bytes(&mut body, &p);
+        tl::write_bytes(&mut body, &q);
+        body.extend_from_slice(&fingerprint.to_le_bytes());
+        tl::write_bytes(&mut body, &encrypted_data);
+
+        self.state = State::ServerDhParams { nonces: Nonces { nonce, server_nonce, new_nonce } };
+        Ok(Step::Send(self.pack(&body, now)))
+    }
+
+    /// server_DH_params_ok: decrypt and validate the DH parameters and send set_client_DH_params
+    fn server_dh_params(&mut self, body: &[u8], nonces: Nonces, now: i64) -> Result<Step> {
+        let mut reader = Reader::new(body);
+        match reader.u32()? {
+            SERVER_DH_PARAMS_OK => {}
+            SERVER_DH_PARAMS_FAIL => {
+                check_nonces(&mut reader, &nonces)?;
+                let hash: [u8; 16] = reader.array()?;
+                if !mtproto::ct_eq(&sha1(&nonces.new_nonce)[4..], &hash) {
+                    return Err(Error::NewNonceHashMismatch);
+                }
+                return Err(Error::ServerDhParamsFail);
+            }
+            constructor => return Err(Error::UnexpectedConstructor(constructor)),
+        }
+        check_nonces(&mut reader, &nonces)?;
+        let encrypted_answer = reader.bytes()?;
+
+        let answer = mtproto::decrypt_server_dh_inner_data(encrypted_answer, &nonces.new_nonce, &nonces.server_nonce)?;
+        let mut reader = Reader::new(&answer);
+        expect(&mut reader, SERVER_DH_INNER_DATA)?;
+        check_nonces(&mut reader, &nonces)?;
+        let g = reader.u32()?;
+        let dh_prime = reader.bytes()?;
+        let g_a = reader.bytes()?;
+        let server_time = reader.i32()?;
+
+        dh::check_dh_params(dh_prime, g)?;
+        dh::check_g_a(g_a, dh_prime)?;
+        self.time_offset = (server_time as i64).saturating_sub(now);
+
+        let params = DhParams { g, dh_prime: dh_prime.to_vec(), g_a: g_a.to_vec() };
+        self.set_client_dh_params(nonces, params, 0, now)
+    }
+
+    /// dh_gen_ok/retry/fail: check new_nonce_hash, then finish or retry with a fresh b
+    fn dh_gen(
+        &mut self,
+        body: &[u8],
+        nonces: Nonces,
+        params: DhParams,
+        auth_key: Box<[u8; DH_PRIME_LEN]>,
+        now: i64,
+    ) -> Result<Step> {
+        let mut reader = Reader::new(body);
+        let status = match reader.u32()? {
+            DH_GEN_OK => DhGenStatus::Ok,
+            DH_GEN_RETRY => DhGenStatus::Retry,
+            DH_GEN_FAIL => DhGenStatus::Fail,
+            constructor => return Err(Error::UnexpectedConstructor(constructor)),
+        };
+        check_nonces(&mut reader, &nonces)?;
+        let hash: [u8; 16] = reader.array()?;
+        mtproto::check_new_nonce_hash(&nonces.new_nonce, &auth_key[..], status, &hash)?;
+
+        match status {
+            DhGenStatus::Ok => Ok(Step::Done(Box::new(AuthKey {
+                auth_key: *auth_key,
+                auth_key_id: mtproto::auth_key_id(&auth_key[..]),
+                server_salt: mtproto::server_salt(&nonces.new_nonce, &nonces.server_nonce),
+                time_offset: self.time_offset,
+                expires_at: match self.kind {
+                    KeyKind::Permanent => None,
+                    KeyKind::Temporary { expires_in } => {
+                        Some(now.saturating_add(self.time_offset).saturating_add(expires_in as i64))
+                    }
+                },
+            }))),
+            DhGenStatus::Retry => {
+                let retry_id = i64::from_le_bytes(mtproto::auth_key_aux_hash(&auth_key[..]));
+                self.set_client_dh_params(nonces, params, retry_id, now)
+            }
+            DhGenStatus::Fail => Err(Error::DhGenFail),
+        }
+    }
+
+    /// Pick a fresh b, compute auth_key and send g_b in set_client_DH_params
+    fn set_client_dh_params(&mut self, nonces: Nonces, params: DhParams, retry_id: i64, now: i64) -> Result<Step> {
+        let mut b = [0u8; DH_PRIME_LEN];
+        rand::fill(&mut b[..]);
+        let g_b = dh::compute_g_b(params.g, &b, &params.dh_prime)?;
+        let auth_key = dh::compute_auth_key(&params.g_a, &b, &params.dh_prime)?;
+
+        let mut inner = Vec::with_capacity(300);
+        inner.extend_from_slice(&CLIENT_DH_INNER_DATA.to_le_bytes());
+        inner.extend_from_slice(&nonces.nonce);
+        inner.extend_from_slice(&nonces.server_nonce);
+        inner.extend_from_slice(&retry_id.to_le_bytes());
+        tl::write_bytes(&mut inner, &g_b);
+        let encrypted_data = mtproto::encrypt_client_dh_inner_data(&inner, &nonces.new_nonce, &nonces.server_nonce)?;
+
+        let mut body = Vec::with_capacity(40 + encrypted_data.len());
+        body.extend_from_slice(&SET_CLIENT_DH_PARAMS.to_le_bytes());
+        body.extend_from_slice(&nonces.nonce);
+        body.extend_from_slice(&nonces.server_nonce);
+        tl::write_bytes(&mut body, &encrypted_data);
+
+        self.state = State::DhGen { nonces, params, auth_key: Box::new(auth_key) };
+        Ok(Step::Send(self.pack(&body, now)))
+    }
+
+    /// Wrap a TL body in a plain message: auth_key_id = 0, msg_id, message_data_length
+    /// msg_id is the server time * 2^32; `now` has no fraction, so the low bits are random,
+    /// non-zero and divisible by 4
+    fn pack(&mut self, body: &[u8], now: i64) -> Vec<u8> {
+        let time = now.saturating_add(self.time_offset).clamp(0, i32::MAX as i64);
+        let fraction = (rand::random::<u32>() & !3).max(4);
+        let msg_id = ((time << 32) | fraction as i64).max(self.last_msg_id.saturating_add(4));
+        self.last_msg_id = msg_id;
+
+        let mut packet = Vec::with_capacity(20 + body.len());
+        packet.extend_from_slice(&[0u8; 8]);
+        packet.extend_from_slice(&msg_id.to_le_bytes());
+        packet.extend_from_slice(&(body.len() as u32).to_le_bytes());
+        packet.extend_from_slice(body);
+        packet
+    }
+}
+
+/// Body of a plain message from the server
+fn unpack(packet: &[u8]) -> Result<&[u8]> {
+    let mut reader = Reader::new(packet);
+    if reader.i64()? != 0 {
+        return Err(Error::AuthKeyIdMismatch);
+    }
+    let _msg_id = reader.i64()?;
+    let len = reader.u32()? as usize;
+    packet[20..].get(..len).ok_or(Error::MalformedTl)
+}
+
+fn expect(reader: &mut Reader, constructor: u32) -> Result<()> {
+    match reader.u32()? {
+        c if c == constructor => Ok(()),
+        c => Err(Error::UnexpectedConstructor(c)),
+    }
+}
+
+fn check_nonce(reader: &mut Reader, nonce: &[u8; 16]) -> Result<()> {
+    if reader.array::<16>()? != *nonce {
+        return Err(Error::NonceMismatch);
+    }
+    Ok(())
+}
+
+fn check_nonces(reader: &mut Reader, nonces: &Nonces) -> Result<()> {
+    check_nonce(reader, &nonces.nonce)?;
+    check_nonce(reader, &nonces.server_nonce)
+}
+
+/// Split the big-endian pq from resPQ into big-endian (p, q) with p < q
+fn factorize_pq(pq: &[u8]) -> Result<(Vec<u8>, Vec<u8>)> {
+    let pq = BigUint::from_bytes_be(pq);
+    // pq is always 64 bits, don't let the server make us factor anything larger
+    if pq.bits() > 64 {
+        return Err(Error::FactorizationFailed);
+    }
+    let (p, q) = factorization::factorize(&pq)?;
+    if !factorization::is_prime(&p) || !factorization::is_prime(&q) {
+        return Err(Error::FactorizationFailed);
+    }
+    Ok((p.to_bytes_be(), q.to_bytes_be()))
+}
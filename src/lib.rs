@@ -1,23 +1,23 @@
-use pyo3::prelude::*;
+//! MTProto cryptography for Telegram clients.
+//!
+//! Everything below is plain Rust working on byte slices, so the crate can be
+//! used as an ordinary rlib. The `tgcrypto` Python extension module is a thin
+//! layer over the same functions, built only with the `python` feature.
 
-mod aes;
-mod factorization;
-mod mtproto;
-mod rsa;
-mod sha;
+pub mod aes;
+pub mod dh;
+pub mod error;
+pub mod factorization;
+pub mod fake_tls;
+pub mod handshake;
+pub mod mtproto;
+pub mod obfuscated;
+pub mod rsa;
+pub mod sha;
+mod tl;
+pub mod transport;
 
-#[pymodule]
-fn tgcrypto(m: &Bound<'_, PyModule>) -> PyResult<()> {
-    m.add_function(wrap_pyfunction!(sha::sha1, m)?)?;
-    m.add_function(wrap_pyfunction!(sha::sha256, m)?)?;
-    m.add_function(wrap_pyfunction!(aes::ige256_encrypt, m)?)?;
-    m.add_function(wrap_pyfunction!(aes::ige256_decrypt, m)?)?;
-    m.add_function(wrap_pyfunction!(aes::ctr256_encrypt, m)?)?;
-    m.add_function(wrap_pyfunction!(aes::ctr256_decrypt, m)?)?;
-    m.add_function(wrap_pyfunction!(aes::cbc256_encrypt, m)?)?;
-    m.add_function(wrap_pyfunction!(aes::cbc256_decrypt, m)?)?;
-    m.add_function(wrap_pyfunction!(rsa::rsa_encrypt, m)?)?;
-    m.add_function(wrap_pyfunction!(factorization::factorize, m)?)?;
-    m.add_function(wrap_pyfunction!(mtproto::get_session_id, m)?)?;
-    Ok(())
-}
+#[cfg(feature = "python")]
+mod python;
+
+pub use error::{Error, Result};
@@ -1,23 +1,455 @@
-use ::sha1::{Digest, Sha1};
-use pyo3::prelude::*;
-use pyo3::types::PyBytes;
+use crate::aes::Ige256;
+use crate::error::{Error, Result};
+use crate::sha::{sha1, sha256};
 
 /// Generate session ID from auth key
 /// The session ID is the first 8 bytes of SHA1(auth_key) in reverse byte order
-#[pyfunction]
-#[pyo3(signature = (auth_key, /))]
-pub fn get_session_id<'py>(py: Python<'py>, auth_key: &[u8]) -> PyResult<Bound<'py, PyBytes>> {
-    let session_id = py.detach(|| {
-        let mut hasher = Sha1::new();
-        hasher.update(auth_key);
-        let hash = hasher.finalize();
-
-        // Take first 8 bytes and reverse them (little-endian)
-        let mut session_id = [0u8; 8];
-        session_id.copy_from_slice(&hash[..8]);
-        session_id.reverse();
-        session_id
-    });
-
-    Ok(PyBytes::new(py, &session_id))
+pub fn get_session_id(auth_key: &[u8]) -> [u8; 8] {
+    let hash = sha1(auth_key);
+
+    // Take first 8 bytes and reverse them (little-endian)
+    let mut session_id = [0u8; 8];
+    session_id.copy_from_slice(&hash[..8]);
+    session_id.reverse();
+    session_id
+}
+
+/// auth_key_aux_hash: the first 8 bytes of SHA1(auth_key)
+pub fn auth_key_aux_hash(auth_key: &[u8]) -> [u8; 8] {
+    let hash = sha1(auth_key);
+    hash[..8].try_into().unwrap()
+}
+
+/// Answer to set_client_DH_params, numbered as in new_nonce_hash1/2/3
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DhGenStatus {
+    Ok = 1,
+    Retry = 2,
+    Fail = 3,
+}
+
+/// new_nonce_hash1/2/3: the lower 128 bits of SHA1(new_nonce + [1|2|3] + auth_key_aux_hash)
+pub fn new_nonce_hash(new_nonce: &[u8; 32], auth_key: &[u8], status: DhGenStatus) -> [u8; 16] {
+    let hash = sha1(&[&new_nonce[..], &[status as u8], &auth_key_aux_hash(auth_key)].concat());
+    hash[4..].try_into().unwrap()
+}
+
+/// Check the new_nonce_hash of a dh_gen_ok, dh_gen_retry or dh_gen_fail answer in constant time
+pub fn check_new_nonce_hash(
+    new_nonce: &[u8; 32],
+    auth_key: &[u8],
+    status: DhGenStatus,
+    hash: &[u8],
+) -> Result<()> {
+    if !ct_eq(&new_nonce_hash(new_nonce, auth_key, status), hash) {
+        return Err(Error::NewNonceHashMismatch);
+    }
+    Ok(())
+}
+
+/// Initial server_salt: new_nonce[0:8] XOR server_nonce[0:8], read as a little-endian integer
+pub fn server_salt(new_nonce: &[u8; 32], server_nonce: &[u8; 16]) -> i64 {
+    let mut salt = [0u8; 8];
+    for (i, byte) in salt.iter_mut().enumerate() {
+        *byte = new_nonce[i] ^ server_nonce[i];
+    }
+    i64::from_le_bytes(salt)
+}
+
+/// Decrypted and validated message sent by the server
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Message {
+    pub salt: i64,
+    pub msg_id: i64,
+    pub seq_no: i32,
+    pub body: Vec<u8>,
+}
+
+/// Decrypt a message sent by the server and validate it before returning anything
+/// Checks msg_key, padding length, message_data_length, session_id and msg_id.
+/// `now` is the current server time in seconds, used for the msg_id window
+pub fn decrypt_server_message(
+    auth_key: &[u8],
+    packet: &[u8],
+    session_id: &[u8],
+    now: i64,
+) -> Result<Message> {
+    let data = decrypt_message(auth_key, packet, 8)?;
+
+    // salt (8) + session_id (8) + msg_id (8) + seq_no (4) + message_data_length (4)
+    if data.len() < 32 + 12 {
+        return Err(Error::BadPadding(data.len().saturating_sub(32)));
+    }
+
+    let length = u32::from_le_bytes(data[28..32].try_into().unwrap()) as usize;
+    if !length.is_multiple_of(4) || length > data.len() - 32 {
+        return Err(Error::BadMessageLength(length));
+    }
+
+    let padding = data.len() - 32 - length;
+    if !(12..=1024).contains(&padding) {
+        return Err(Error::BadPadding(padding));
+    }
+
+    if !ct_eq(&data[8..16], session_id) {
+        return Err(Error::SessionMismatch);
+    }
+
+    // Server msg_ids are odd and roughly unixtime * 2^32
+    let msg_id = i64::from_le_bytes(data[16..24].try_into().unwrap());
+    let msg_time = msg_id >> 32;
+    if msg_id % 2 == 0 || msg_time < now.saturating_sub(300) || msg_time > now.saturating_add(30) {
+        return Err(Error::BadMsgId(msg_id));
+    }
+
+    Ok(Message {
+        salt: i64::from_le_bytes(data[..8].try_into().unwrap()),
+        msg_id,
+        seq_no: i32::from_le_bytes(data[24..28].try_into().unwrap()),
+        body: data[32..32 + length].to_vec(),
+    })
+}
+
+/// Auth key ID: the lower 64 bits of SHA1(auth_key)
+pub fn auth_key_id(auth_key: &[u8]) -> [u8; 8] {
+    let hash = sha1(auth_key);
+    hash[12..].try_into().unwrap()
+}
+
+/// Compare two byte strings without an early exit on the first difference
+pub(crate) fn ct_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+fn check_auth_key(auth_key: &[u8], x: usize) -> Result<()> {
+    if auth_key.len() != 256 {
+        return Err(Error::InvalidAuthKeyLength(auth_key.len()));
+    }
+    if x != 0 && x != 8 {
+        return Err(Error::InvalidDirection(x));
+    }
+    Ok(())
+}
+
+/// SHA256(auth_key[88+x:120+x] + plaintext), the source of msg_key and the quick-ACK token
+fn msg_key_large(auth_key: &[u8], plaintext: &[u8], x: usize) -> [u8; 32] {
+    let mut data = Vec::with_capacity(32 + plaintext.len());
+    data.extend_from_slice(&auth_key[88 + x..120 + x]);
+    data.extend_from_slice(plaintext);
+    sha256(&data)
+}
+
+/// MTProto 2.0 msg_key: SHA256(auth_key[88+x:120+x] + plaintext)[8:24]
+fn compute_msg_key(auth_key: &[u8], plaintext: &[u8], x: usize) -> [u8; 16] {
+    msg_key_large(auth_key, plaintext, x)[8..24].try_into().unwrap()
+}
+
+/// MTProto 2.0 KDF deriving (aes_key, aes_iv) from auth_key and msg_key
+fn kdf(auth_key: &[u8], msg_key: &[u8; 16], x: usize) -> ([u8; 32], [u8; 32]) {
+    let mut data = Vec::with_capacity(52);
+    data.extend_from_slice(msg_key);
+    data.extend_from_slice(&auth_key[x..x + 36]);
+    let sha256_a = sha256(&data);
+
+    data.clear();
+    data.extend_from_slice(&auth_key[40 + x..76 + x]);
+    data.extend_from_slice(msg_key);
+    let sha256_b = sha256(&data);
+
+    let mut aes_key = [0u8; 32];
+    aes_key[..8].copy_from_slice(&sha256_a[..8]);
+    aes_key[8..24].copy_from_slice(&sha256_b[8..24]);
+    aes_key[24..].copy_from_slice(&sha256_a[24..]);
+
+    let mut aes_iv = [0u8; 32];
+    aes_iv[..8].copy_from_slice(&sha256_b[..8]);
+    aes_iv[8..24].copy_from_slice(&sha256_a[8..24]);
+    aes_iv[24..].copy_from_slice(&sha256_b[24..]);
+
+    (aes_key, aes_iv)
+}
+
+/// MTProto 2.0 message encryption
+/// Pads the plaintext with 12..=27 random bytes and returns auth_key_id + msg_key + encrypted_data.
+/// `x` is 0 for messages sent by the client and 8 for messages sent by the server
+pub fn encrypt_message(auth_key: &[u8], plaintext: &[u8], x: usize) -> Result<Vec<u8>> {
+    Ok(encrypt_message_quick_ack(auth_key, plaintext, x)?.0)
+}
+
+/// MTProto 2.0 message encryption that also returns the expected quick-ACK token
+/// The token is the first 32 bits of the msg_key SHA-256 (padding included) with the MSB set
+pub fn encrypt_message_quick_ack(auth_key: &[u8], plaintext: &[u8], x: usize) -> Result<(Vec<u8>, u32)> {
+    check_auth_key(auth_key, x)?;
+
+    let padding_len = 12 + (16 - (plaintext.len() + 12) % 16) % 16;
+    let mut data = Vec::with_capacity(plaintext.len() + padding_len);
+    data.extend_from_slice(plaintext);
+    data.resize(plaintext.len() + padding_len, 0);
+    rand::fill(&mut data[plaintext.len()..]);
+
+    let hash = msg_key_large(auth_key, &data, x);
+    let quick_ack = u32::from_le_bytes(hash[..4].try_into().unwrap()) | 1 << 31;
+    let msg_key: [u8; 16] = hash[8..24].try_into().unwrap();
+    let (aes_key, aes_iv) = kdf(auth_key, &msg_key, x);
+    Ige256::new(&aes_key)?.encrypt(&mut data, &aes_iv)?;
+
+    let mut packet = Vec::with_capacity(24 + data.len());
+    packet.extend_from_slice(&auth_key_id(auth_key));
+    packet.extend_from_slice(&msg_key);
+    packet.extend_from_slice(&data);
+    Ok((packet, quick_ack))
+}
+
+/// MTProto 2.0 message decryption
+/// Checks auth_key_id and msg_key and returns the decrypted data, padding included.
+/// `x` is 0 for messages sent by the client and 8 for messages sent by the server
+pub fn decrypt_message(auth_key: &[u8], packet: &[u8], x: usize) -> Result<Vec<u8>> {
+    check_auth_key(auth_key, x)?;
+
+    if packet.len() < 24 + 16 || !(packet.len() - 24).is_multiple_of(16) {
+        return Err(Error::InvalidPacketLength(packet.len()));
+    }
+    if !ct_eq(&packet[..8], &auth_key_id(auth_key)) {
+        return Err(Error::AuthKeyIdMismatch);
+    }
+
+    let msg_key: [u8; 16] = packet[8..24].try_into().unwrap();
+    let (aes_key, aes_iv) = kdf(auth_key, &msg_key, x);
+    let mut data = packet[24..].to_vec();
+    Ige256::new(&aes_key)?.decrypt(&mut data, &aes_iv)?;
+
+    if !ct_eq(&compute_msg_key(auth_key, &data, x), &msg_key) {
+        return Err(Error::MsgKeyMismatch);
+    }
+
+    Ok(data)
+}
+
+/// MTProto 1.0 KDF deriving (aes_key, aes_iv) from auth_key and msg_key
+/// `x` is 0 for messages sent by the client and 8 for messages sent by the server
+pub fn kdf_v1(auth_key: &[u8], msg_key: &[u8; 16], x: usize) -> Result<([u8; 32], [u8; 32])> {
+    check_auth_key(auth_key, x)?;
+
+    let sha1_a = sha1(&[msg_key, &auth_key[x..x + 32]].concat());
+    let sha1_b = sha1(&[&auth_key[32 + x..48 + x], msg_key, &auth_key[48 + x..64 + x]].concat());
+    let sha1_c = sha1(&[&auth_key[64 + x..96 + x], msg_key].concat());
+    let sha1_d = sha1(&[msg_key, &auth_key[96 + x..128 + x]].concat());
+
+    let mut aes_key = [0u8; 32];
+    aes_key[..8].copy_from_slice(&sha1_a[..8]);
+    aes_key[8..20].copy_from_slice(&sha1_b[8..20]);
+    aes_key[20..].copy_from_slice(&sha1_c[4..16]);
+
+    let mut aes_iv = [0u8; 32];
+    aes_iv[..12].copy_from_slice(&sha1_a[8..20]);
+    aes_iv[12..20].copy_from_slice(&sha1_b[..8]);
+    aes_iv[20..24].copy_from_slice(&sha1_c[16..20]);
+    aes_iv[24..].copy_from_slice(&sha1_d[..8]);
+
+    Ok((aes_key, aes_iv))
+}
+
+/// MTProto 1.0 message encryption
+/// msg_key is SHA1(plaintext)[4:20], computed before the 0..=15 random padding bytes are added.
+/// Returns auth_key_id + msg_key + encrypted_data
+pub fn encrypt_message_v1(auth_key: &[u8], plaintext: &[u8], x: usize) -> Result<Vec<u8>> {
+    let mut padding = [0u8; 16];
+    rand::fill(&mut padding[..]);
+    encrypt_message_v1_padded(auth_key, plaintext, x, &padding[..(16 - plaintext.len() % 16) % 16])
+}
+
+fn encrypt_message_v1_padded(auth_key: &[u8], plaintext: &[u8], x: usize, padding: &[u8]) -> Result<Vec<u8>> {
+    check_auth_key(auth_key, x)?;
+
+    let msg_key: [u8; 16] = sha1(plaintext)[4..20].try_into().unwrap();
+    let (aes_key, aes_iv) = kdf_v1(auth_key, &msg_key, x)?;
+
+    let mut data = Vec::with_capacity(plaintext.len() + padding.len());
+    data.extend_from_slice(plaintext);
+    data.extend_from_slice(padding);
+    Ige256::new(&aes_key)?.encrypt(&mut data, &aes_iv)?;
+
+    let mut packet = Vec::with_capacity(24 + data.len());
+    packet.extend_from_slice(&auth_key_id(auth_key));
+    packet.extend_from_slice(&msg_key);
+    packet.extend_from_slice(&data);
+    Ok(packet)
+}
+
+/// MTProto 1.0 message decryption
+/// The unpadded length is taken from message_data_length at offset 28, as in MTProto messages.
+/// Returns the decrypted message without padding once msg_key has been checked
+pub fn decrypt_message_v1(auth_key: &[u8], packet: &[u8], x: usize) -> Result<Vec<u8>> {
+    decrypt_message_v1_at(auth_key, packet, x, 28)
+}
+
+/// MTProto 1.0 secret chat encryption
+/// The payload is prefixed with its int32 length, which msg_key covers.
+/// Returns auth_key_id + msg_key + encrypted_data
+pub fn encrypt_secret_message_v1(auth_key: &[u8], payload: &[u8], x: usize) -> Result<Vec<u8>> {
+    let mut plaintext = Vec::with_capacity(4 + payload.len());
+    plaintext.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    plaintext.extend_from_slice(payload);
+    encrypt_message_v1(auth_key, &plaintext, x)
+}
+
+/// MTProto 1.0 secret chat decryption
+/// The unpadded length is taken from the int32 prefix at offset 0.
+/// Returns the payload without its length prefix and padding once msg_key has been checked
+pub fn decrypt_secret_message_v1(auth_key: &[u8], packet: &[u8], x: usize) -> Result<Vec<u8>> {
+    let mut data = decrypt_message_v1_at(auth_key, packet, x, 0)?;
+    data.drain(..4);
+    Ok(data)
+}
+
+/// MTProto 1.0 decryption with the int32 message length at `length_offset`
+/// Returns everything up to the end of the message, padding removed
+fn decrypt_message_v1_at(auth_key: &[u8], packet: &[u8], x: usize, length_offset: usize) -> Result<Vec<u8>> {
+    check_auth_key(auth_key, x)?;
+
+    let header = length_offset + 4;
+    if packet.len() < 24 + header.next_multiple_of(16) || !(packet.len() - 24).is_multiple_of(16) {
+        return Err(Error::InvalidPacketLength(packet.len()));
+    }
+    if !ct_eq(&packet[..8], &auth_key_id(auth_key)) {
+        return Err(Error::AuthKeyIdMismatch);
+    }
+
+    let msg_key: [u8; 16] = packet[8..24].try_into().unwrap();
+    let (aes_key, aes_iv) = kdf_v1(auth_key, &msg_key, x)?;
+    let mut data = packet[24..].to_vec();
+    Ige256::new(&aes_key)?.decrypt(&mut data, &aes_iv)?;
+
+    // Only the padding may follow the message, so at most 15 bytes
+    let length = u32::from_le_bytes(data[length_offset..header].try_into().unwrap()) as usize;
+    if length > data.len() - header || data.len() - header - length > 15 {
+        return Err(Error::BadMessageLength(length));
+    }
+    data.truncate(header + length);
+
+    if !ct_eq(&sha1(&data)[4..20], &msg_key) {
+        return Err(Error::MsgKeyMismatch);
+    }
+
+    Ok(data)
+}
+
+/// bind_auth_key_inner#75a3f765
+const BIND_AUTH_KEY_INNER: u32 = 0x75a3f765;
+
+/// encrypted_message for auth.bindTempAuthKey
+/// bind_auth_key_inner is sent as an MTProto 1.0 message encrypted with the permanent key.
+/// `msg_id` should be the msg_id of the outer auth.bindTempAuthKey message, a random one is used if None
+pub fn encrypt_bind_temp_auth_key(
+    perm_key: &[u8],
+    temp_key_id: i64,
+    nonce: i64,
+    expires_at: i32,
+    temp_session_id: i64,
+    msg_id: Option<i64>,
+) -> Result<Vec<u8>> {
+    let mut random = [0u8; 32];
+    rand::fill(&mut random[..]);
+    let msg_id = msg_id.unwrap_or(i64::from_le_bytes(random[24..].try_into().unwrap()));
+    encrypt_bind_temp_auth_key_with_random(
+        perm_key,
+        temp_key_id,
+        nonce,
+        expires_at,
+        temp_session_id,
+        msg_id,
+        random[..24].try_into().unwrap(),
+    )
+}
+
+/// Build encrypted_message from caller-provided random bytes
+/// `random` is the random:int128 header followed by the 8 padding bytes
+pub fn encrypt_bind_temp_auth_key_with_random(
+    perm_key: &[u8],
+    temp_key_id: i64,
+    nonce: i64,
+    expires_at: i32,
+    temp_session_id: i64,
+    msg_id: i64,
+    random: [u8; 24],
+) -> Result<Vec<u8>> {
+    // random:int128 + msg_id + seqno = 0 + msg_len = 40 + bind_auth_key_inner
+    let mut plaintext = Vec::with_capacity(72);
+    plaintext.extend_from_slice(&random[..16]);
+    plaintext.extend_from_slice(&msg_id.to_le_bytes());
+    plaintext.extend_from_slice(&0u32.to_le_bytes());
+    plaintext.extend_from_slice(&40u32.to_le_bytes());
+    plaintext.extend_from_slice(&BIND_AUTH_KEY_INNER.to_le_bytes());
+    plaintext.extend_from_slice(&nonce.to_le_bytes());
+    plaintext.extend_from_slice(&temp_key_id.to_le_bytes());
+    plaintext.extend_from_slice(&auth_key_id(perm_key));
+    plaintext.extend_from_slice(&temp_session_id.to_le_bytes());
+    plaintext.extend_from_slice(&expires_at.to_le_bytes());
+
+    encrypt_message_v1_padded(perm_key, &plaintext, 0, &random[16..])
+}
+
+/// Temporary AES key and IV protecting the DH exchange, derived from the handshake nonces
+pub fn tmp_aes_key_iv(new_nonce: &[u8; 32], server_nonce: &[u8; 16]) -> ([u8; 32], [u8; 32]) {
+    let new_server = sha1(&[&new_nonce[..], server_nonce].concat());
+    let server_new = sha1(&[&server_nonce[..], new_nonce].concat());
+    let new_new = sha1(&[&new_nonce[..], new_nonce].concat());
+
+    // tmp_aes_key = SHA1(new_nonce + server_nonce) + SHA1(server_nonce + new_nonce)[0:12]
+    let mut tmp_aes_key = [0u8; 32];
+    tmp_aes_key[..20].copy_from_slice(&new_server);
+    tmp_aes_key[20..].copy_from_slice(&server_new[..12]);
+
+    // tmp_aes_iv = SHA1(server_nonce + new_nonce)[12:20] + SHA1(new_nonce + new_nonce) + new_nonce[0:4]
+    let mut tmp_aes_iv = [0u8; 32];
+    tmp_aes_iv[..8].copy_from_slice(&server_new[12..]);
+    tmp_aes_iv[8..28].copy_from_slice(&new_new);
+    tmp_aes_iv[28..].copy_from_slice(&new_nonce[..4]);
+
+    (tmp_aes_key, tmp_aes_iv)
+}
+
+/// Decrypt the encrypted_answer of server_DH_params_ok
+/// The answer is SHA1(data) + data + 0..=15 random bytes; the data is returned once its hash matches
+pub fn decrypt_server_dh_inner_data(
+    encrypted_answer: &[u8],
+    new_nonce: &[u8; 32],
+    server_nonce: &[u8; 16],
+) -> Result<Vec<u8>> {
+    if encrypted_answer.len() < 32 || !encrypted_answer.len().is_multiple_of(16) {
+        return Err(Error::InvalidPacketLength(encrypted_answer.len()));
+    }
+
+    let (tmp_aes_key, tmp_aes_iv) = tmp_aes_key_iv(new_nonce, server_nonce);
+    let mut answer_with_hash = encrypted_answer.to_vec();
+    Ige256::new(&tmp_aes_key)?.decrypt(&mut answer_with_hash, &tmp_aes_iv)?;
+
+    // The data length is not sent, so try every possible padding length
+    let (hash, answer) = answer_with_hash.split_at(20);
+    let found = (0..16.min(answer.len() + 1))
+        .map(|padding| &answer[..answer.len() - padding])
+        .find(|data| ct_eq(&sha1(data), hash));
+
+    match found {
+        Some(data) => Ok(data.to_vec()),
+        None => Err(Error::DhAnswerHashMismatch),
+    }
+}
+
+/// Encrypt client_DH_inner_data for set_client_DH_params
+/// SHA1(data) + data is padded with random bytes to a multiple of 16 and IGE-encrypted with the tmp key
+pub fn encrypt_client_dh_inner_data(data: &[u8], new_nonce: &[u8; 32], server_nonce: &[u8; 16]) -> Result<Vec<u8>> {
+    let len = (20 + data.len()).next_multiple_of(16);
+    let mut data_with_hash = Vec::with_capacity(len);
+    data_with_hash.extend_from_slice(&sha1(data));
+    data_with_hash.extend_from_slice(data);
+    data_with_hash.resize(len, 0);
+    rand::fill(&mut data_with_hash[20 + data.len()..]);
+
+    let (tmp_aes_key, tmp_aes_iv) = tmp_aes_key_iv(new_nonce, server_nonce);
+    Ige256::new(&tmp_aes_key)?.encrypt(&mut data_with_hash, &tmp_aes_iv)?;
+    Ok(data_with_hash)
 }
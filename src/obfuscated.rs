@@ -0,0 +1,203 @@
+use crate::aes::CtrStream;
+use crate::error::{Error, Result};
+use crate::sha::sha256;
+
+/// Protocol tag of the abridged transport
+pub const ABRIDGED: [u8; 4] = [0xef; 4];
+/// Protocol tag of the intermediate transport
+pub const INTERMEDIATE: [u8; 4] = [0xee; 4];
+/// Protocol tag of the padded intermediate transport
+pub const PADDED_INTERMEDIATE: [u8; 4] = [0xdd; 4];
+
+/// First 4 bytes the server would take for another protocol
+const FORBIDDEN_PREFIXES: [[u8; 4]; 8] = [
+    *b"HEAD",
+    *b"POST",
+    *b"GET ",
+    *b"OPTI",
+    *b"PVrG",
+    [0xdd; 4],
+    [0xee; 4],
+    [0x16, 0x03, 0x01, 0x02],
+];
+
+fn check_tag(tag: &[u8]) -> Result<[u8; 4]> {
+    match <[u8; 4]>::try_from(tag) {
+        Ok(tag) if [ABRIDGED, INTERMEDIATE, PADDED_INTERMEDIATE].contains(&tag) => Ok(tag),
+        _ => Err(Error::InvalidProtocolTag),
+    }
+}
+
+/// MTProxy secret as 16 raw bytes, accepting the 0xdd-prefixed form
+pub(crate) fn proxy_secret(secret: &[u8]) -> Result<[u8; 16]> {
+    let raw = match secret.len() {
+        17 if secret[0] == 0xdd => &secret[1..],
+        _ => secret,
+    };
+    raw.try_into().map_err(|_| Error::InvalidSecretLength(secret.len()))
+}
+
+/// Whether random bytes can be sent as an init header
+fn is_valid_init(init: &[u8; 64]) -> bool {
+    init[0] != 0xef && !FORBIDDEN_PREFIXES.iter().any(|p| init[..4] == p[..]) && init[4..8] != [0u8; 4]
+}
+
+/// Derive the (encrypt, decrypt) CTR streams seen by the side that sent `init`
+/// The key and IV are init[8:56], and the same bytes reversed for the other direction
+pub(crate) fn derive_streams(init: &[u8; 64], secret: Option<&[u8; 16]>) -> Result<(CtrStream, CtrStream)> {
+    let mut reversed = [0u8; 48];
+    reversed.copy_from_slice(&init[8..56]);
+    reversed.reverse();
+
+    let stream = |key_iv: &[u8]| {
+        let key = match secret {
+            Some(secret) => sha256(&[&key_iv[..32], &secret[..]].concat()),
+            None => key_iv[..32].try_into().unwrap(),
+        };
+        CtrStream::new(&key, &key_iv[32..48], 0)
+    };
+
+    Ok((stream(&init[8..56])?, stream(&reversed)?))
+}
+
+/// Client side of the obfuscated2 transport
+/// Holds the 64-byte init header and one AES-256-CTR stream per direction
+#[derive(Clone)]
+pub struct Obfuscated2 {
+    init_packet: [u8; 64],
+    encryptor: CtrStream,
+    decryptor: CtrStream,
+}
+
+impl Obfuscated2 {
+    /// Generate a fresh init header for the protocol `tag` and `dc_id`
+    /// `secret` is an optional MTProxy secret (16 bytes, or 17 with the 0xdd prefix)
+    pub fn new(tag: &[u8], dc_id: i16, secret: Option<&[u8]>) -> Result<Self> {
+        let mut random = [0u8; 64];
+        loop {
+            rand::fill(&mut random[..]);
+            if is_valid_init(&random) {
+                return Self::with_random(random, tag, dc_id, secret);
+            }
+        }
+    }
+
+    /// Build the init header from caller-provided random bytes
+    pub fn with_random(random: [u8; 64], tag: &[u8], dc_id: i16, secret: Option<&[u8]>) -> Result<Self> {
+        let tag = check_tag(tag)?;
+        let secret = secret.map(proxy_secret).transpose()?;
+        if !is_valid_init(&random) {
+            return Err(Error::InvalidInitPacket);
+        }
+
+        let mut init = random;
+        init[56..60].copy_from_slice(&tag);
+        init[60..62].copy_from_slice(&dc_id.to_le_bytes());
+
+        let (mut encryptor, decryptor) = derive_streams(&init, secret.as_ref())?;
+
+        // Only the tail carrying the tag and DC id is sent encrypted
+        let mut encrypted = init;
+        encryptor.process(&mut encrypted);
+        let mut init_packet = init;
+        init_packet[56..].copy_from_slice(&encrypted[56..]);
+
+        Ok(Self { init_packet, encryptor, decryptor })
+    }
+
+    /// The 64 bytes to send before any other data
+    pub fn init_packet(&self) -> &[u8; 64] {
+        &self.init_packet
+    }
+
+    /// Stream for outgoing data, already advanced past the init header
+    pub fn encryptor(&mut self) -> &mut CtrStream {
+        &mut self.encryptor
+    }
+
+    /// Stream for incoming data
+    pub fn decryptor(&mut self) -> &mut CtrStream {
+        &mut self.decryptor
+    }
+
+    /// Split into the (encryptor, decryptor) streams
+    pub fn into_streams(self) -> (CtrStream, CtrStream) {
+        (self.encryptor, self.decryptor)
+    }
+}
+
+/// Server side of the obfuscated2 transport
+/// Identifies the secret the client used and holds one AES-256-CTR stream per direction
+#[derive(Clone)]
+pub struct Obfuscated2Server {
+    secret_index: usize,
+    tag: [u8; 4],
+    dc_id: i16,
+    encryptor: CtrStream,
+    decryptor: CtrStream,
+}
+
+impl Obfuscated2Server {
+    /// Validate a client's 64-byte init header against each of `secrets` in turn
+    /// An empty secret matches clients that connect without one, malformed secrets never match
+    pub fn accept(init: &[u8], secrets: &[&[u8]]) -> Result<Self> {
+        let init: &[u8; 64] = init.try_into().map_err(|_| Error::InvalidInitPacket)?;
+
+        for (secret_index, secret) in secrets.iter().enumerate() {
+            let secret = match secret.is_empty() {
+                true => None,
+                false => match proxy_secret(secret) {
+                    Ok(secret) => Some(secret),
+                    Err(_) => continue,
+                },
+            };
+
+            // Our decryptor is the client's encryptor and vice versa
+            let (mut decryptor, encryptor) = derive_streams(init, secret.as_ref())?;
+            let mut header = *init;
+            decryptor.process(&mut header);
+
+            if let Ok(tag) = check_tag(&header[56..60]) {
+                return Ok(Self {
+                    secret_index,
+                    tag,
+                    dc_id: i16::from_le_bytes([header[60], header[61]]),
+                    encryptor,
+                    decryptor,
+                });
+            }
+        }
+
+        Err(Error::UnknownSecret)
+    }
+
+    /// Index of the secret the client used
+    pub fn secret_index(&self) -> usize {
+        self.secret_index
+    }
+
+    /// Protocol tag requested by the client
+    pub fn tag(&self) -> [u8; 4] {
+        self.tag
+    }
+
+    /// DC id requested by the client, negative for media DCs
+    pub fn dc_id(&self) -> i16 {
+        self.dc_id
+    }
+
+    /// Stream for outgoing data
+    pub fn encryptor(&mut self) -> &mut CtrStream {
+        &mut self.encryptor
+    }
+
+    /// Stream for incoming data, already advanced past the init header
+    pub fn decryptor(&mut self) -> &mut CtrStream {
+        &mut self.decryptor
+    }
+
+    /// Split into the (encryptor, decryptor) streams
+    pub fn into_streams(self) -> (CtrStream, CtrStream) {
+        (self.encryptor, self.decryptor)
+    }
+}
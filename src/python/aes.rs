@@ -0,0 +1,246 @@
+use pyo3::prelude::*;
+use pyo3::types::{PyByteArray, PyBytes};
+
+use crate::aes;
+use crate::error::Error;
+
+/// Write `value` back into `obj` if the caller passed a bytearray of the same size
+fn update_bytearray(obj: Bound<'_, PyAny>, value: &[u8]) {
+    if let Ok(array) = obj.cast_into::<PyByteArray>() {
+        if array.len() == value.len() {
+            // SAFETY: no Python code runs while the slice is borrowed
+            let slice = unsafe { array.as_bytes_mut() };
+            slice.copy_from_slice(value);
+        }
+    }
+}
+
+/// AES-256-IGE Encryption
+#[pyfunction]
+#[pyo3(signature = (data, key, iv, /))]
+pub fn ige256_encrypt<'py>(
+    py: Python<'py>,
+    data: &[u8],
+    key: Bound<'py, PyAny>,
+    iv: Bound<'py, PyAny>,
+) -> PyResult<Bound<'py, PyBytes>> {
+    // Convert key and iv (bytes or bytearray) to Vec<u8>
+    let key_bytes: Vec<u8> = key.extract()?;
+    let iv_bytes: Vec<u8> = iv.extract()?;
+
+    let mut result = data.to_vec();
+    py.detach(|| aes::ige256_encrypt(&mut result, &key_bytes, &iv_bytes))?;
+
+    Ok(PyBytes::new(py, &result))
+}
+
+/// AES-256-IGE Decryption
+#[pyfunction]
+#[pyo3(signature = (data, key, iv, /))]
+pub fn ige256_decrypt<'py>(
+    py: Python<'py>,
+    data: &[u8],
+    key: Bound<'py, PyAny>,
+    iv: Bound<'py, PyAny>,
+) -> PyResult<Bound<'py, PyBytes>> {
+    // Convert key and iv (bytes or bytearray) to Vec<u8>
+    let key_bytes: Vec<u8> = key.extract()?;
+    let iv_bytes: Vec<u8> = iv.extract()?;
+
+    let mut result = data.to_vec();
+    py.detach(|| aes::ige256_decrypt(&mut result, &key_bytes, &iv_bytes))?;
+
+    Ok(PyBytes::new(py, &result))
+}
+
+/// AES-256-CTR Encryption/Decryption
+/// This matches the pyaes implementation where state[0] is the position in the keystream block
+#[pyfunction]
+#[pyo3(signature = (data, key, iv, state, /))]
+pub fn ctr256_encrypt<'py>(
+    py: Python<'py>,
+    data: Bound<'py, PyAny>,
+    key: Bound<'py, PyAny>,
+    iv: Bound<'py, PyAny>,
+    state: Bound<'py, PyAny>,
+) -> PyResult<Bound<'py, PyBytes>> {
+    // Convert data, key and iv (bytes or bytearray) to Vec<u8>
+    let key_bytes: Vec<u8> = key.extract()?;
+    let mut result: Vec<u8> = data.extract()?;
+    let mut iv_bytes: Vec<u8> = iv.extract()?;
+
+    // Extract state position (state[0] is position in keystream block 0-15)
+    let state_bytes: Vec<u8> = state.extract()?;
+    if state_bytes.len() != 1 {
+        return Err(Error::InvalidStateLength(state_bytes.len()).into());
+    }
+    let mut ks_pos = state_bytes[0];
+
+    py.detach(|| aes::ctr256_encrypt(&mut result, &key_bytes, &mut iv_bytes, &mut ks_pos))?;
+
+    // Update state bytearray
+    update_bytearray(state, &[ks_pos]);
+
+    // Update IV bytearray
+    update_bytearray(iv, &iv_bytes);
+
+    Ok(PyBytes::new(py, &result))
+}
+
+/// AES-256-CTR Decryption
+#[pyfunction]
+#[pyo3(signature = (data, key, iv, state, /))]
+pub fn ctr256_decrypt<'py>(
+    py: Python<'py>,
+    data: Bound<'py, PyAny>,
+    key: Bound<'py, PyAny>,
+    iv: Bound<'py, PyAny>,
+    state: Bound<'py, PyAny>,
+) -> PyResult<Bound<'py, PyBytes>> {
+    // CTR mode is symmetric
+    ctr256_encrypt(py, data, key, iv, state)
+}
+
+/// AES-256-CBC Encryption
+#[pyfunction]
+#[pyo3(signature = (data, key, iv, /))]
+pub fn cbc256_encrypt<'py>(
+    py: Python<'py>,
+    data: &[u8],
+    key: Bound<'py, PyAny>,
+    iv: Bound<'py, PyAny>,
+) -> PyResult<Bound<'py, PyBytes>> {
+    // Convert key and iv (bytes or bytearray) to Vec<u8>
+    let key_bytes: Vec<u8> = key.extract()?;
+    let mut iv_bytes: Vec<u8> = iv.extract()?;
+
+    let mut result = data.to_vec();
+    py.detach(|| aes::cbc256_encrypt(&mut result, &key_bytes, &mut iv_bytes))?;
+
+    // Update IV bytearray
+    update_bytearray(iv, &iv_bytes);
+
+    Ok(PyBytes::new(py, &result))
+}
+
+/// AES-256-CBC Decryption
+#[pyfunction]
+#[pyo3(signature = (data, key, iv, /))]
+pub fn cbc256_decrypt<'py>(
+    py: Python<'py>,
+    data: &[u8],
+    key: Bound<'py, PyAny>,
+    iv: Bound<'py, PyAny>,
+) -> PyResult<Bound<'py, PyBytes>> {
+    // Convert key and iv (bytes or bytearray) to Vec<u8>
+    let key_bytes: Vec<u8> = key.extract()?;
+    let mut iv_bytes: Vec<u8> = iv.extract()?;
+
+    let mut result = data.to_vec();
+    py.detach(|| aes::cbc256_decrypt(&mut result, &key_bytes, &mut iv_bytes))?;
+
+    // Update IV bytearray
+    update_bytearray(iv, &iv_bytes);
+
+    Ok(PyBytes::new(py, &result))
+}
+
+/// AES-256-IGE cipher holding an expanded key
+#[pyclass(name = "IgeCipher", module = "tgcrypto", frozen)]
+pub struct IgeCipher {
+    inner: aes::Ige256,
+}
+
+#[pymethods]
+impl IgeCipher {
+    #[new]
+    #[pyo3(signature = (key, /))]
+    fn new(key: &[u8]) -> PyResult<Self> {
+        Ok(Self { inner: aes::Ige256::new(key)? })
+    }
+
+    /// AES-256-IGE Encryption
+    #[pyo3(signature = (data, iv, /))]
+    fn encrypt<'py>(&self, py: Python<'py>, data: &[u8], iv: &[u8]) -> PyResult<Bound<'py, PyBytes>> {
+        let mut result = data.to_vec();
+        py.detach(|| self.inner.encrypt(&mut result, iv))?;
+        Ok(PyBytes::new(py, &result))
+    }
+
+    /// AES-256-IGE Decryption
+    #[pyo3(signature = (data, iv, /))]
+    fn decrypt<'py>(&self, py: Python<'py>, data: &[u8], iv: &[u8]) -> PyResult<Bound<'py, PyBytes>> {
+        let mut result = data.to_vec();
+        py.detach(|| self.inner.decrypt(&mut result, iv))?;
+        Ok(PyBytes::new(py, &result))
+    }
+
+    /// Start a resumable encryption for a payload processed in chunks
+    #[pyo3(signature = (iv, /))]
+    fn encryptor(&self, iv: &[u8]) -> PyResult<IgeStream> {
+        Ok(IgeStream { inner: self.inner.encryptor(iv)? })
+    }
+
+    /// Start a resumable decryption for a payload processed in chunks
+    #[pyo3(signature = (iv, /))]
+    fn decryptor(&self, iv: &[u8]) -> PyResult<IgeStream> {
+        Ok(IgeStream { inner: self.inner.decryptor(iv)? })
+    }
+}
+
+/// Resumable AES-256-IGE state carrying (iv1, iv2) across chunks
+#[pyclass(name = "IgeStream", module = "tgcrypto")]
+pub struct IgeStream {
+    inner: aes::IgeStream,
+}
+
+#[pymethods]
+impl IgeStream {
+    /// Process the next chunk, its length must be a multiple of 16
+    #[pyo3(signature = (data, /))]
+    fn update<'py>(&mut self, py: Python<'py>, data: &[u8]) -> PyResult<Bound<'py, PyBytes>> {
+        let mut result = data.to_vec();
+        py.detach(|| self.inner.update(&mut result))?;
+        Ok(PyBytes::new(py, &result))
+    }
+
+    /// Current 32-byte IV, usable to continue with ige256_encrypt/ige256_decrypt
+    #[getter]
+    fn iv<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, &self.inner.iv())
+    }
+}
+
+/// AES-256-CTR stream owning its counter and keystream position
+#[pyclass(name = "CtrStream", module = "tgcrypto")]
+pub struct CtrStream {
+    pub(crate) inner: aes::CtrStream,
+}
+
+#[pymethods]
+impl CtrStream {
+    #[new]
+    #[pyo3(signature = (key, iv, offset = 0))]
+    fn new(key: &[u8], iv: &[u8], offset: u64) -> PyResult<Self> {
+        Ok(Self { inner: aes::CtrStream::new(key, iv, offset)? })
+    }
+
+    /// Encrypt or decrypt the next bytes of the stream
+    #[pyo3(signature = (data, /))]
+    fn process<'py>(&mut self, py: Python<'py>, data: &[u8]) -> Bound<'py, PyBytes> {
+        let mut result = data.to_vec();
+        py.detach(|| self.inner.process(&mut result));
+        PyBytes::new(py, &result)
+    }
+
+    /// Move to an absolute byte offset in the keystream
+    #[pyo3(signature = (offset, /))]
+    fn seek(&mut self, offset: u64) {
+        self.inner.seek(offset);
+    }
+
+    /// Current byte offset in the keystream
+    fn tell(&self) -> u64 {
+        self.inner.tell()
+    }
+}
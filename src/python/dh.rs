@@ -0,0 +1,50 @@
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+
+use crate::dh;
+
+/// Check that dh_prime is a 2048-bit safe prime
+/// Primes that pass are cached, so repeated checks are cheap
+#[pyfunction]
+#[pyo3(signature = (dh_prime, /))]
+pub fn check_dh_prime(py: Python<'_>, dh_prime: &[u8]) -> PyResult<()> {
+    py.detach(|| dh::check_dh_prime(dh_prime))?;
+    Ok(())
+}
+
+/// Check dh_prime and that g is a valid generator for it
+#[pyfunction]
+#[pyo3(signature = (dh_prime, g, /))]
+pub fn check_dh_params(py: Python<'_>, dh_prime: &[u8], g: u32) -> PyResult<()> {
+    py.detach(|| dh::check_dh_params(dh_prime, g))?;
+    Ok(())
+}
+
+/// Check that g_a (or g_b) lies in [2^1984, dh_prime - 2^1984]
+#[pyfunction]
+#[pyo3(signature = (g_a, dh_prime, /))]
+pub fn check_g_a(py: Python<'_>, g_a: &[u8], dh_prime: &[u8]) -> PyResult<()> {
+    py.detach(|| dh::check_g_a(g_a, dh_prime))?;
+    Ok(())
+}
+
+/// Compute g_b = g^b mod dh_prime as 256 big-endian bytes
+#[pyfunction]
+#[pyo3(signature = (g, b, dh_prime, /))]
+pub fn compute_g_b<'py>(py: Python<'py>, g: u32, b: &[u8], dh_prime: &[u8]) -> PyResult<Bound<'py, PyBytes>> {
+    let g_b = py.detach(|| dh::compute_g_b(g, b, dh_prime))?;
+    Ok(PyBytes::new(py, &g_b))
+}
+
+/// Compute auth_key = g_a^b mod dh_prime as 256 big-endian bytes
+#[pyfunction]
+#[pyo3(signature = (g_a, b, dh_prime, /))]
+pub fn compute_auth_key<'py>(
+    py: Python<'py>,
+    g_a: &[u8],
+    b: &[u8],
+    dh_prime: &[u8],
+) -> PyResult<Bound<'py, PyBytes>> {
+    let auth_key = py.detach(|| dh::compute_auth_key(g_a, b, dh_prime))?;
+    Ok(PyBytes::new(py, &auth_key))
+}
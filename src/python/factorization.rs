@@ -0,0 +1,72 @@
+use num_bigint::{BigInt, BigUint};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+use crate::factorization;
+
+/// pq as a Python int of any size, or as the big-endian bytes sent in resPQ
+#[derive(FromPyObject)]
+pub enum Pq {
+    Int(BigInt),
+    Bytes(Vec<u8>),
+}
+
+impl Pq {
+    fn into_biguint(self, name: &str) -> PyResult<BigUint> {
+        match self {
+            Pq::Int(pq) => pq.to_biguint().ok_or_else(|| PyValueError::new_err(format!("{name} must be positive"))),
+            Pq::Bytes(pq) => Ok(BigUint::from_bytes_be(&pq)),
+        }
+    }
+}
+
+/// Split pq into its two factors using Pollard's rho algorithm
+/// Returns (p, q) with p <= q
+#[pyfunction]
+#[pyo3(signature = (pq, /, seed = None, max_iterations = None))]
+pub fn factorize(
+    py: Python<'_>,
+    pq: Pq,
+    seed: Option<u64>,
+    max_iterations: Option<u64>,
+) -> PyResult<(BigUint, BigUint)> {
+    let (p, q, _, _) = factorize_with_iterations(py, pq, seed, max_iterations)?;
+    Ok((p, q))
+}
+
+/// `factorize` that also returns the number of iterations it took and the seed that replays the run
+/// Returns (p, q, iterations, seed)
+#[pyfunction]
+#[pyo3(signature = (pq, /, seed = None, max_iterations = None))]
+pub fn factorize_with_iterations(
+    py: Python<'_>,
+    pq: Pq,
+    seed: Option<u64>,
+    max_iterations: Option<u64>,
+) -> PyResult<(BigUint, BigUint, u64, u64)> {
+    let pq = pq.into_biguint("pq")?;
+    let result = py.detach(|| factorization::factorize_with(&pq, seed, max_iterations))?;
+    Ok((result.p, result.q, result.iterations, result.seed))
+}
+
+/// Deterministic primality test, Miller-Rabin below 2^64 and Baillie-PSW above
+#[pyfunction]
+#[pyo3(signature = (n, /))]
+pub fn is_prime(py: Python<'_>, n: Pq) -> PyResult<bool> {
+    let n = n.into_biguint("n")?;
+    Ok(py.detach(|| factorization::is_prime(&n)))
+}
+
+/// Factorize n completely, with the same seed and max_iterations as factorize
+/// Returns [(prime, exponent), ...] in increasing order of the primes
+#[pyfunction]
+#[pyo3(signature = (n, /, seed = None, max_iterations = None))]
+pub fn factor_all(
+    py: Python<'_>,
+    n: Pq,
+    seed: Option<u64>,
+    max_iterations: Option<u64>,
+) -> PyResult<Vec<(BigUint, u32)>> {
+    let n = n.into_biguint("n")?;
+    Ok(py.detach(|| factorization::factor_all_with(&n, seed, max_iterations))?)
+}
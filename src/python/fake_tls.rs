@@ -0,0 +1,106 @@
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+
+use crate::fake_tls;
+
+/// Client side of the MTProxy fake-TLS transport
+#[pyclass(name = "FakeTlsClient", module = "tgcrypto")]
+pub struct FakeTlsClient {
+    inner: fake_tls::FakeTlsClient,
+}
+
+#[pymethods]
+impl FakeTlsClient {
+    #[new]
+    #[pyo3(signature = (secret, tag, dc_id))]
+    fn new(secret: &[u8], tag: &[u8], dc_id: i16) -> PyResult<Self> {
+        Ok(Self { inner: fake_tls::FakeTlsClient::new(secret, tag, dc_id)? })
+    }
+
+    /// The ClientHello record to send first
+    #[getter]
+    fn client_hello<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, self.inner.client_hello())
+    }
+
+    /// Check the server's response to the ClientHello
+    /// Returns the number of bytes it spans, or None if more data is needed
+    #[pyo3(signature = (response, /))]
+    fn verify_server_hello(&self, response: &[u8]) -> PyResult<Option<usize>> {
+        Ok(self.inner.verify_server_hello(response)?)
+    }
+
+    /// Encrypt outgoing data and wrap it in application data records
+    #[pyo3(signature = (data, /))]
+    fn encrypt<'py>(&mut self, py: Python<'py>, data: &[u8]) -> Bound<'py, PyBytes> {
+        let result = py.detach(|| self.inner.encrypt(data));
+        PyBytes::new(py, &result)
+    }
+
+    /// Unwrap incoming records and decrypt their payload
+    #[pyo3(signature = (data, /))]
+    fn decrypt<'py>(&mut self, py: Python<'py>, data: &[u8]) -> PyResult<Bound<'py, PyBytes>> {
+        let result = py.detach(|| self.inner.decrypt(data))?;
+        Ok(PyBytes::new(py, &result))
+    }
+}
+
+/// Server side of the MTProxy fake-TLS transport
+#[pyclass(name = "FakeTlsServer", module = "tgcrypto")]
+pub struct FakeTlsServer {
+    inner: fake_tls::FakeTlsServer,
+}
+
+#[pymethods]
+impl FakeTlsServer {
+    #[new]
+    #[pyo3(signature = (client_hello, secrets, max_skew = 120))]
+    fn new(client_hello: &[u8], secrets: Vec<Vec<u8>>, max_skew: u32) -> PyResult<Self> {
+        let secrets: Vec<&[u8]> = secrets.iter().map(Vec::as_slice).collect();
+        Ok(Self { inner: fake_tls::FakeTlsServer::accept(client_hello, &secrets, max_skew)? })
+    }
+
+    /// Index of the secret the client used
+    #[getter]
+    fn secret_index(&self) -> usize {
+        self.inner.secret_index()
+    }
+
+    /// Unix time the client put in its ClientHello
+    #[getter]
+    fn timestamp(&self) -> u32 {
+        self.inner.timestamp()
+    }
+
+    /// The records to send back in reply to the ClientHello
+    #[getter]
+    fn server_hello<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, self.inner.server_hello())
+    }
+
+    /// Protocol tag requested by the client, or None before the init header
+    #[getter]
+    fn tag<'py>(&self, py: Python<'py>) -> Option<Bound<'py, PyBytes>> {
+        self.inner.tag().map(|tag| PyBytes::new(py, &tag))
+    }
+
+    /// DC id requested by the client, or None before the init header
+    #[getter]
+    fn dc_id(&self) -> Option<i16> {
+        self.inner.dc_id()
+    }
+
+    /// Encrypt outgoing data and wrap it in application data records
+    #[pyo3(signature = (data, /))]
+    fn encrypt<'py>(&mut self, py: Python<'py>, data: &[u8]) -> PyResult<Bound<'py, PyBytes>> {
+        let result = py.detach(|| self.inner.encrypt(data))?;
+        Ok(PyBytes::new(py, &result))
+    }
+
+    /// Unwrap incoming records and decrypt their payload
+    #[pyo3(signature = (data, /))]
+    fn decrypt<'py>(&mut self, py: Python<'py>, data: &[u8]) -> PyResult<Bound<'py, PyBytes>> {
+        let result = py.detach(|| self.inner.decrypt(data))?;
+        Ok(PyBytes::new(py, &result))
+    }
+}
@@ -0,0 +1,83 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+
+use crate::handshake::{self, AuthKey, KeyKind, Step};
+
+fn now() -> i64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs() as i64)
+}
+
+/// Sans-IO auth key creation, from req_pq_multi to dh_gen_ok
+/// Messages are plain MTProto messages, to be framed with a transport codec
+#[pyclass(name = "AuthKeyHandshake", module = "tgcrypto")]
+pub struct AuthKeyHandshake {
+    inner: handshake::AuthKeyHandshake,
+    result: Option<AuthKey>,
+}
+
+#[pymethods]
+impl AuthKeyHandshake {
+    /// A temporary key is created when `expires_in` is given
+    #[new]
+    #[pyo3(signature = (dc_id, expires_in = None))]
+    fn new(dc_id: i32, expires_in: Option<i32>) -> Self {
+        let kind = match expires_in {
+            Some(expires_in) => KeyKind::Temporary { expires_in },
+            None => KeyKind::Permanent,
+        };
+        Self { inner: handshake::AuthKeyHandshake::new(dc_id, kind), result: None }
+    }
+
+    /// Start (or restart) the handshake and return req_pq_multi
+    fn start<'py>(&mut self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        self.result = None;
+        PyBytes::new(py, &self.inner.start(now()))
+    }
+
+    /// Process a server message and return the next one to send, or None once the key is created
+    #[pyo3(signature = (packet, /))]
+    fn feed<'py>(&mut self, py: Python<'py>, packet: &[u8]) -> PyResult<Option<Bound<'py, PyBytes>>> {
+        match py.detach(|| self.inner.feed(packet, now()))? {
+            Step::Send(packet) => Ok(Some(PyBytes::new(py, &packet))),
+            Step::Done(key) => {
+                self.result = Some(*key);
+                Ok(None)
+            }
+        }
+    }
+
+    /// Whether the auth key has been created
+    #[getter]
+    fn done(&self) -> bool {
+        self.result.is_some()
+    }
+
+    #[getter]
+    fn auth_key<'py>(&self, py: Python<'py>) -> Option<Bound<'py, PyBytes>> {
+        self.result.as_ref().map(|key| PyBytes::new(py, &key.auth_key))
+    }
+
+    #[getter]
+    fn auth_key_id<'py>(&self, py: Python<'py>) -> Option<Bound<'py, PyBytes>> {
+        self.result.as_ref().map(|key| PyBytes::new(py, &key.auth_key_id))
+    }
+
+    #[getter]
+    fn server_salt(&self) -> Option<i64> {
+        self.result.as_ref().map(|key| key.server_salt)
+    }
+
+    /// Server time minus local time, in seconds
+    #[getter]
+    fn time_offset(&self) -> Option<i64> {
+        self.result.as_ref().map(|key| key.time_offset)
+    }
+
+    /// Server time a temporary key expires at
+    #[getter]
+    fn expires_at(&self) -> Option<i64> {
+        self.result.as_ref().and_then(|key| key.expires_at)
+    }
+}
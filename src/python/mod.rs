@@ -0,0 +1,91 @@
+use pyo3::exceptions::PyValueError;
+use pyo3::create_exception;
+use pyo3::prelude::*;
+
+use crate::error::Error;
+
+mod aes;
+mod dh;
+mod factorization;
+mod fake_tls;
+mod handshake;
+mod mtproto;
+mod obfuscated;
+mod rsa;
+mod sha;
+mod transport;
+
+create_exception!(tgcrypto, IterationLimitError, PyValueError, "Factorization used up its max_iterations");
+
+impl From<Error> for PyErr {
+    fn from(err: Error) -> PyErr {
+        match err {
+            Error::IterationLimit { iterations, seed } => Python::attach(|py| {
+                let exc = IterationLimitError::new_err(err.to_string());
+                let value = exc.value(py);
+                match value.setattr("iterations", iterations).and_then(|_| value.setattr("seed", seed)) {
+                    Ok(()) => exc,
+                    Err(e) => e,
+                }
+            }),
+            _ => PyValueError::new_err(err.to_string()),
+        }
+    }
+}
+
+#[pymodule]
+fn tgcrypto(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(sha::sha1, m)?)?;
+    m.add_function(wrap_pyfunction!(sha::sha256, m)?)?;
+    m.add_function(wrap_pyfunction!(aes::ige256_encrypt, m)?)?;
+    m.add_function(wrap_pyfunction!(aes::ige256_decrypt, m)?)?;
+    m.add_function(wrap_pyfunction!(aes::ctr256_encrypt, m)?)?;
+    m.add_function(wrap_pyfunction!(aes::ctr256_decrypt, m)?)?;
+    m.add_function(wrap_pyfunction!(aes::cbc256_encrypt, m)?)?;
+    m.add_function(wrap_pyfunction!(aes::cbc256_decrypt, m)?)?;
+    m.add_class::<obfuscated::Obfuscated2>()?;
+    m.add_class::<obfuscated::Obfuscated2Server>()?;
+    m.add_class::<fake_tls::FakeTlsClient>()?;
+    m.add_class::<fake_tls::FakeTlsServer>()?;
+    m.add_class::<aes::IgeCipher>()?;
+    m.add_class::<aes::IgeStream>()?;
+    m.add_class::<aes::CtrStream>()?;
+    m.add_class::<transport::TransportCodec>()?;
+    m.add_class::<handshake::AuthKeyHandshake>()?;
+    m.add_function(wrap_pyfunction!(rsa::rsa_encrypt, m)?)?;
+    m.add_function(wrap_pyfunction!(rsa::rsa_pad_encrypt, m)?)?;
+    m.add_function(wrap_pyfunction!(rsa::add_public_key, m)?)?;
+    m.add_function(wrap_pyfunction!(rsa::remove_public_key, m)?)?;
+    m.add_function(wrap_pyfunction!(rsa::list_public_keys, m)?)?;
+    m.add_function(wrap_pyfunction!(rsa::add_test_dc_keys, m)?)?;
+    m.add_function(wrap_pyfunction!(rsa::rsa_fingerprint, m)?)?;
+    m.add_function(wrap_pyfunction!(factorization::factorize, m)?)?;
+    m.add_function(wrap_pyfunction!(factorization::factorize_with_iterations, m)?)?;
+    m.add_function(wrap_pyfunction!(factorization::is_prime, m)?)?;
+    m.add_function(wrap_pyfunction!(factorization::factor_all, m)?)?;
+    m.add("IterationLimitError", m.py().get_type::<IterationLimitError>())?;
+    m.add_function(wrap_pyfunction!(mtproto::get_session_id, m)?)?;
+    m.add_function(wrap_pyfunction!(mtproto::auth_key_aux_hash, m)?)?;
+    m.add_function(wrap_pyfunction!(mtproto::new_nonce_hash, m)?)?;
+    m.add_function(wrap_pyfunction!(mtproto::check_new_nonce_hash, m)?)?;
+    m.add_function(wrap_pyfunction!(mtproto::server_salt, m)?)?;
+    m.add_function(wrap_pyfunction!(mtproto::decrypt_server_message, m)?)?;
+    m.add_function(wrap_pyfunction!(mtproto::encrypt_message, m)?)?;
+    m.add_function(wrap_pyfunction!(mtproto::encrypt_message_quick_ack, m)?)?;
+    m.add_function(wrap_pyfunction!(mtproto::decrypt_message, m)?)?;
+    m.add_function(wrap_pyfunction!(mtproto::kdf_v1, m)?)?;
+    m.add_function(wrap_pyfunction!(mtproto::encrypt_message_v1, m)?)?;
+    m.add_function(wrap_pyfunction!(mtproto::decrypt_message_v1, m)?)?;
+    m.add_function(wrap_pyfunction!(mtproto::encrypt_secret_message_v1, m)?)?;
+    m.add_function(wrap_pyfunction!(mtproto::decrypt_secret_message_v1, m)?)?;
+    m.add_function(wrap_pyfunction!(mtproto::tmp_aes_key_iv, m)?)?;
+    m.add_function(wrap_pyfunction!(mtproto::decrypt_server_dh_inner_data, m)?)?;
+    m.add_function(wrap_pyfunction!(mtproto::encrypt_client_dh_inner_data, m)?)?;
+    m.add_function(wrap_pyfunction!(mtproto::encrypt_bind_temp_auth_key, m)?)?;
+    m.add_function(wrap_pyfunction!(dh::check_dh_prime, m)?)?;
+    m.add_function(wrap_pyfunction!(dh::check_dh_params, m)?)?;
+    m.add_function(wrap_pyfunction!(dh::check_g_a, m)?)?;
+    m.add_function(wrap_pyfunction!(dh::compute_g_b, m)?)?;
+    m.add_function(wrap_pyfunction!(dh::compute_auth_key, m)?)?;
+    Ok(())
+}
@@ -0,0 +1,248 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+
+use crate::mtproto::{self, DhGenStatus};
+
+/// Generate session ID from auth key
+/// The session ID is the first 8 bytes of SHA1(auth_key) in reverse byte order
+#[pyfunction]
+#[pyo3(signature = (auth_key, /))]
+pub fn get_session_id<'py>(py: Python<'py>, auth_key: &[u8]) -> PyResult<Bound<'py, PyBytes>> {
+    let session_id = py.detach(|| mtproto::get_session_id(auth_key));
+    Ok(PyBytes::new(py, &session_id))
+}
+
+/// First 8 bytes of SHA1(auth_key)
+#[pyfunction]
+#[pyo3(signature = (auth_key, /))]
+pub fn auth_key_aux_hash<'py>(py: Python<'py>, auth_key: &[u8]) -> Bound<'py, PyBytes> {
+    PyBytes::new(py, &mtproto::auth_key_aux_hash(auth_key))
+}
+
+/// 1 for dh_gen_ok, 2 for dh_gen_retry and 3 for dh_gen_fail
+fn dh_gen_status(number: u8) -> PyResult<DhGenStatus> {
+    match number {
+        1 => Ok(DhGenStatus::Ok),
+        2 => Ok(DhGenStatus::Retry),
+        3 => Ok(DhGenStatus::Fail),
+        _ => Err(PyValueError::new_err(format!("new_nonce_hash number must be 1, 2 or 3, got {}", number))),
+    }
+}
+
+/// new_nonce_hash1/2/3 expected in dh_gen_ok/retry/fail
+#[pyfunction]
+#[pyo3(signature = (new_nonce, auth_key, number, /))]
+pub fn new_nonce_hash<'py>(
+    py: Python<'py>,
+    new_nonce: [u8; 32],
+    auth_key: &[u8],
+    number: u8,
+) -> PyResult<Bound<'py, PyBytes>> {
+    let hash = mtproto::new_nonce_hash(&new_nonce, auth_key, dh_gen_status(number)?);
+    Ok(PyBytes::new(py, &hash))
+}
+
+/// Check the new_nonce_hash of a dh_gen_ok/retry/fail answer in constant time
+#[pyfunction]
+#[pyo3(signature = (new_nonce, auth_key, number, hash, /))]
+pub fn check_new_nonce_hash(new_nonce: [u8; 32], auth_key: &[u8], number: u8, hash: &[u8]) -> PyResult<()> {
+    mtproto::check_new_nonce_hash(&new_nonce, auth_key, dh_gen_status(number)?, hash)?;
+    Ok(())
+}
+
+/// Initial server_salt derived from new_nonce and server_nonce
+#[pyfunction]
+#[pyo3(signature = (new_nonce, server_nonce, /))]
+pub fn server_salt(new_nonce: [u8; 32], server_nonce: [u8; 16]) -> i64 {
+    mtproto::server_salt(&new_nonce, &server_nonce)
+}
+
+/// Decrypt a message sent by the server and validate it before returning anything
+/// Returns (salt, msg_id, seq_no, body)
+#[pyfunction]
+#[pyo3(signature = (auth_key, packet, session_id, time_offset = 0))]
+pub fn decrypt_server_message<'py>(
+    py: Python<'py>,
+    auth_key: &[u8],
+    packet: &[u8],
+    session_id: &[u8],
+    time_offset: i64,
+) -> PyResult<(i64, i64, i32, Bound<'py, PyBytes>)> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs() as i64);
+    let message = py.detach(|| {
+        mtproto::decrypt_server_message(auth_key, packet, session_id, now + time_offset)
+    })?;
+    Ok((message.salt, message.msg_id, message.seq_no, PyBytes::new(py, &message.body)))
+}
+
+/// MTProto 2.0 message encryption
+/// Returns auth_key_id + msg_key + encrypted_data
+#[pyfunction]
+#[pyo3(signature = (auth_key, plaintext, x, /))]
+pub fn encrypt_message<'py>(
+    py: Python<'py>,
+    auth_key: &[u8],
+    plaintext: &[u8],
+    x: usize,
+) -> PyResult<Bound<'py, PyBytes>> {
+    let packet = py.detach(|| mtproto::encrypt_message(auth_key, plaintext, x))?;
+    Ok(PyBytes::new(py, &packet))
+}
+
+/// MTProto 2.0 message encryption that also returns the expected quick-ACK token
+#[pyfunction]
+#[pyo3(signature = (auth_key, plaintext, x, /))]
+pub fn encrypt_message_quick_ack<'py>(
+    py: Python<'py>,
+    auth_key: &[u8],
+    plaintext: &[u8],
+    x: usize,
+) -> PyResult<(Bound<'py, PyBytes>, u32)> {
+    let (packet, quick_ack) = py.detach(|| mtproto::encrypt_message_quick_ack(auth_key, plaintext, x))?;
+    Ok((PyBytes::new(py, &packet), quick_ack))
+}
+
+/// MTProto 2.0 message decryption
+/// Checks auth_key_id and msg_key and returns the decrypted data, padding included
+#[pyfunction]
+#[pyo3(signature = (auth_key, packet, x, /))]
+pub fn decrypt_message<'py>(
+    py: Python<'py>,
+    auth_key: &[u8],
+    packet: &[u8],
+    x: usize,
+) -> PyResult<Bound<'py, PyBytes>> {
+    let data = py.detach(|| mtproto::decrypt_message(auth_key, packet, x))?;
+    Ok(PyBytes::new(py, &data))
+}
+
+/// MTProto 1.0 KDF
+/// Returns (aes_key, aes_iv)
+#[pyfunction]
+#[pyo3(signature = (auth_key, msg_key, x, /))]
+pub fn kdf_v1<'py>(
+    py: Python<'py>,
+    auth_key: &[u8],
+    msg_key: [u8; 16],
+    x: usize,
+) -> PyResult<(Bound<'py, PyBytes>, Bound<'py, PyBytes>)> {
+    let (aes_key, aes_iv) = mtproto::kdf_v1(auth_key, &msg_key, x)?;
+    Ok((PyBytes::new(py, &aes_key), PyBytes::new(py, &aes_iv)))
+}
+
+/// MTProto 1.0 message encryption
+/// Returns auth_key_id + msg_key + encrypted_data
+#[pyfunction]
+#[pyo3(signature = (auth_key, plaintext, x, /))]
+pub fn encrypt_message_v1<'py>(
+    py: Python<'py>,
+    auth_key: &[u8],
+    plaintext: &[u8],
+    x: usize,
+) -> PyResult<Bound<'py, PyBytes>> {
+    let packet = py.detach(|| mtproto::encrypt_message_v1(auth_key, plaintext, x))?;
+    Ok(PyBytes::new(py, &packet))
+}
+
+/// MTProto 1.0 message decryption
+/// Returns the decrypted message without padding once msg_key has been checked
+#[pyfunction]
+#[pyo3(signature = (auth_key, packet, x, /))]
+pub fn decrypt_message_v1<'py>(
+    py: Python<'py>,
+    auth_key: &[u8],
+    packet: &[u8],
+    x: usize,
+) -> PyResult<Bound<'py, PyBytes>> {
+    let data = py.detach(|| mtproto::decrypt_message_v1(auth_key, packet, x))?;
+    Ok(PyBytes::new(py, &data))
+}
+
+/// MTProto 1.0 secret chat encryption, prefixing the payload with its int32 length
+/// Returns auth_key_id + msg_key + encrypted_data
+#[pyfunction]
+#[pyo3(signature = (auth_key, payload, x = 0, /))]
+pub fn encrypt_secret_message_v1<'py>(
+    py: Python<'py>,
+    auth_key: &[u8],
+    payload: &[u8],
+    x: usize,
+) -> PyResult<Bound<'py, PyBytes>> {
+    let packet = py.detach(|| mtproto::encrypt_secret_message_v1(auth_key, payload, x))?;
+    Ok(PyBytes::new(py, &packet))
+}
+
+/// MTProto 1.0 secret chat decryption
+/// Returns the payload without its length prefix and padding once msg_key has been checked
+#[pyfunction]
+#[pyo3(signature = (auth_key, packet, x = 0, /))]
+pub fn decrypt_secret_message_v1<'py>(
+    py: Python<'py>,
+    auth_key: &[u8],
+    packet: &[u8],
+    x: usize,
+) -> PyResult<Bound<'py, PyBytes>> {
+    let payload = py.detach(|| mtproto::decrypt_secret_message_v1(auth_key, packet, x))?;
+    Ok(PyBytes::new(py, &payload))
+}
+
+/// Temporary AES key and IV protecting the DH exchange
+/// Returns (tmp_aes_key, tmp_aes_iv)
+#[pyfunction]
+#[pyo3(signature = (new_nonce, server_nonce, /))]
+pub fn tmp_aes_key_iv<'py>(
+    py: Python<'py>,
+    new_nonce: [u8; 32],
+    server_nonce: [u8; 16],
+) -> (Bound<'py, PyBytes>, Bound<'py, PyBytes>) {
+    let (tmp_aes_key, tmp_aes_iv) = mtproto::tmp_aes_key_iv(&new_nonce, &server_nonce);
+    (PyBytes::new(py, &tmp_aes_key), PyBytes::new(py, &tmp_aes_iv))
+}
+
+/// Decrypt the encrypted_answer of server_DH_params_ok and check its SHA-1
+#[pyfunction]
+#[pyo3(signature = (encrypted_answer, new_nonce, server_nonce, /))]
+pub fn decrypt_server_dh_inner_data<'py>(
+    py: Python<'py>,
+    encrypted_answer: &[u8],
+    new_nonce: [u8; 32],
+    server_nonce: [u8; 16],
+) -> PyResult<Bound<'py, PyBytes>> {
+    let data = py.detach(|| mtproto::decrypt_server_dh_inner_data(encrypted_answer, &new_nonce, &server_nonce))?;
+    Ok(PyBytes::new(py, &data))
+}
+
+/// Encrypt client_DH_inner_data for set_client_DH_params
+#[pyfunction]
+#[pyo3(signature = (data, new_nonce, server_nonce, /))]
+pub fn encrypt_client_dh_inner_data<'py>(
+    py: Python<'py>,
+    data: &[u8],
+    new_nonce: [u8; 32],
+    server_nonce: [u8; 16],
+) -> PyResult<Bound<'py, PyBytes>> {
+    let encrypted = py.detach(|| mtproto::encrypt_client_dh_inner_data(data, &new_nonce, &server_nonce))?;
+    Ok(PyBytes::new(py, &encrypted))
+}
+
+/// encrypted_message for auth.bindTempAuthKey, encrypted with the permanent key using MTProto 1.0
+/// `msg_id` should be the msg_id of the outer message, a random one is used if None
+#[pyfunction]
+#[pyo3(signature = (perm_key, temp_key_id, nonce, expires_at, temp_session_id, /, msg_id = None))]
+pub fn encrypt_bind_temp_auth_key<'py>(
+    py: Python<'py>,
+    perm_key: &[u8],
+    temp_key_id: i64,
+    nonce: i64,
+    expires_at: i32,
+    temp_session_id: i64,
+    msg_id: Option<i64>,
+) -> PyResult<Bound<'py, PyBytes>> {
+    let encrypted = py.detach(|| {
+        mtproto::encrypt_bind_temp_auth_key(perm_key, temp_key_id, nonce, expires_at, temp_session_id, msg_id)
+    })?;
+    Ok(PyBytes::new(py, &encrypted))
+}
@@ -0,0 +1,108 @@
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+
+use super::aes::CtrStream;
+use crate::obfuscated;
+
+/// Client side of the obfuscated2 transport
+#[pyclass(name = "Obfuscated2", module = "tgcrypto", frozen)]
+pub struct Obfuscated2 {
+    init_packet: [u8; 64],
+    encryptor: Py<CtrStream>,
+    decryptor: Py<CtrStream>,
+}
+
+#[pymethods]
+impl Obfuscated2 {
+    #[new]
+    #[pyo3(signature = (tag, dc_id, secret = None))]
+    fn new(py: Python<'_>, tag: &[u8], dc_id: i16, secret: Option<&[u8]>) -> PyResult<Self> {
+        let obfuscated = obfuscated::Obfuscated2::new(tag, dc_id, secret)?;
+        let init_packet = *obfuscated.init_packet();
+        let (encryptor, decryptor) = obfuscated.into_streams();
+
+        Ok(Self {
+            init_packet,
+            encryptor: Py::new(py, CtrStream { inner: encryptor })?,
+            decryptor: Py::new(py, CtrStream { inner: decryptor })?,
+        })
+    }
+
+    /// The 64 bytes to send before any other data
+    #[getter]
+    fn init_packet<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, &self.init_packet)
+    }
+
+    /// Stream for outgoing data, already advanced past the init header
+    #[getter]
+    fn encryptor(&self, py: Python<'_>) -> Py<CtrStream> {
+        self.encryptor.clone_ref(py)
+    }
+
+    /// Stream for incoming data
+    #[getter]
+    fn decryptor(&self, py: Python<'_>) -> Py<CtrStream> {
+        self.decryptor.clone_ref(py)
+    }
+}
+
+/// Server side of the obfuscated2 transport
+#[pyclass(name = "Obfuscated2Server", module = "tgcrypto", frozen)]
+pub struct Obfuscated2Server {
+    secret_index: usize,
+    tag: [u8; 4],
+    dc_id: i16,
+    encryptor: Py<CtrStream>,
+    decryptor: Py<CtrStream>,
+}
+
+#[pymethods]
+impl Obfuscated2Server {
+    #[new]
+    #[pyo3(signature = (init_packet, secrets))]
+    fn new(py: Python<'_>, init_packet: &[u8], secrets: Vec<Vec<u8>>) -> PyResult<Self> {
+        let secrets: Vec<&[u8]> = secrets.iter().map(Vec::as_slice).collect();
+        let server = obfuscated::Obfuscated2Server::accept(init_packet, &secrets)?;
+        let (secret_index, tag, dc_id) = (server.secret_index(), server.tag(), server.dc_id());
+        let (encryptor, decryptor) = server.into_streams();
+
+        Ok(Self {
+            secret_index,
+            tag,
+            dc_id,
+            encryptor: Py::new(py, CtrStream { inner: encryptor })?,
+            decryptor: Py::new(py, CtrStream { inner: decryptor })?,
+        })
+    }
+
+    /// Index of the secret the client used
+    #[getter]
+    fn secret_index(&self) -> usize {
+        self.secret_index
+    }
+
+    /// Protocol tag requested by the client
+    #[getter]
+    fn tag<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, &self.tag)
+    }
+
+    /// DC id requested by the client
+    #[getter]
+    fn dc_id(&self) -> i16 {
+        self.dc_id
+    }
+
+    /// Stream for outgoing data
+    #[getter]
+    fn encryptor(&self, py: Python<'_>) -> Py<CtrStream> {
+        self.encryptor.clone_ref(py)
+    }
+
+    /// Stream for incoming data, already advanced past the init header
+    #[getter]
+    fn decryptor(&self, py: Python<'_>) -> Py<CtrStream> {
+        self.decryptor.clone_ref(py)
+    }
+}
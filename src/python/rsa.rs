@@ -0,0 +1,65 @@
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+
+use crate::rsa;
+
+/// RSA encrypt using Telegram server public key
+/// `padding` is None for raw RSA or "sha1" for the legacy SHA1(data) + data + random scheme
+#[pyfunction]
+#[pyo3(signature = (data, fingerprint, /, padding = None))]
+pub fn rsa_encrypt<'py>(
+    py: Python<'py>,
+    data: &[u8],
+    fingerprint: i64,
+    padding: Option<&str>,
+) -> PyResult<Bound<'py, PyBytes>> {
+    let padding = match padding {
+        None => rsa::Padding::None,
+        Some("sha1") => rsa::Padding::Sha1,
+        Some(other) => return Err(PyValueError::new_err(format!("Unknown padding: {}", other))),
+    };
+    let result = py.detach(|| rsa::rsa_encrypt_padded(data, fingerprint, padding))?;
+    Ok(PyBytes::new(py, &result))
+}
+
+/// RSA_PAD encryption of p_q_inner_data for req_DH_params
+#[pyfunction]
+#[pyo3(signature = (data, fingerprint, /))]
+pub fn rsa_pad_encrypt<'py>(py: Python<'py>, data: &[u8], fingerprint: i64) -> PyResult<Bound<'py, PyBytes>> {
+    let result = py.detach(|| rsa::rsa_pad_encrypt(data, fingerprint))?;
+    Ok(PyBytes::new(py, &result))
+}
+
+/// Register a PEM encoded RSA public key and return its fingerprint
+#[pyfunction]
+#[pyo3(signature = (pem, /))]
+pub fn add_public_key(pem: &str) -> PyResult<i64> {
+    Ok(rsa::add_public_key(pem)?)
+}
+
+/// Unregister an RSA public key, returning whether it was registered
+#[pyfunction]
+#[pyo3(signature = (fingerprint, /))]
+pub fn remove_public_key(fingerprint: i64) -> bool {
+    rsa::remove_public_key(fingerprint)
+}
+
+/// Fingerprints of every registered RSA public key
+#[pyfunction]
+pub fn list_public_keys() -> Vec<i64> {
+    rsa::list_public_keys()
+}
+
+/// Register the test DC public keys and return their fingerprints
+#[pyfunction]
+pub fn add_test_dc_keys() -> Vec<i64> {
+    rsa::add_test_dc_keys()
+}
+
+/// Telegram fingerprint of a PKCS#1 or SPKI PEM encoded RSA public key
+#[pyfunction]
+#[pyo3(signature = (pem, /))]
+pub fn rsa_fingerprint(pem: &str) -> PyResult<i64> {
+    Ok(rsa::rsa_fingerprint(pem)?)
+}
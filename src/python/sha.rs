@@ -0,0 +1,20 @@
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+
+use crate::sha;
+
+/// Compute SHA-1 hash
+#[pyfunction]
+#[pyo3(signature = (data, /))]
+pub fn sha1<'py>(py: Python<'py>, data: &[u8]) -> PyResult<Bound<'py, PyBytes>> {
+    let result = py.detach(|| sha::sha1(data));
+    Ok(PyBytes::new(py, &result))
+}
+
+/// Compute SHA-256 hash
+#[pyfunction]
+#[pyo3(signature = (data, /))]
+pub fn sha256<'py>(py: Python<'py>, data: &[u8]) -> PyResult<Bound<'py, PyBytes>> {
+    let result = py.detach(|| sha::sha256(data));
+    Ok(PyBytes::new(py, &result))
+}
@@ -0,0 +1,58 @@
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+
+use crate::transport::{self, Frame, Transport};
+
+/// Sans-IO framing for the abridged, intermediate, padded intermediate and full transports
+#[pyclass(name = "TransportCodec", module = "tgcrypto")]
+pub struct TransportCodec {
+    inner: transport::Codec,
+}
+
+#[pymethods]
+impl TransportCodec {
+    #[new]
+    #[pyo3(signature = (transport))]
+    fn new(transport: &str) -> PyResult<Self> {
+        let transport = match transport {
+            "abridged" => Transport::Abridged,
+            "intermediate" => Transport::Intermediate,
+            "padded_intermediate" => Transport::PaddedIntermediate,
+            "full" => Transport::Full,
+            _ => return Err(PyValueError::new_err(format!("Unknown transport: {}", transport))),
+        };
+        Ok(Self { inner: transport::Codec::new(transport) })
+    }
+
+    /// Bytes a client sends first on a plain TCP connection
+    #[getter]
+    fn header<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, self.inner.transport().header())
+    }
+
+    /// Frame an outgoing packet, optionally requesting a quick ACK
+    #[pyo3(signature = (packet, /, quick_ack = false))]
+    fn encode<'py>(&mut self, py: Python<'py>, packet: &[u8], quick_ack: bool) -> PyResult<Bound<'py, PyBytes>> {
+        let result = match quick_ack {
+            true => self.inner.encode_quick_ack(packet)?,
+            false => self.inner.encode(packet)?,
+        };
+        Ok(PyBytes::new(py, &result))
+    }
+
+    /// Feed received bytes and return every complete packet
+    /// Transport errors are returned as negative ints and quick-ACK tokens as ints >= 2**31
+    #[pyo3(signature = (data, /))]
+    fn feed<'py>(&mut self, py: Python<'py>, data: &[u8]) -> PyResult<Vec<Bound<'py, PyAny>>> {
+        let frames = py.detach(|| self.inner.feed(data))?;
+        frames
+            .into_iter()
+            .map(|frame| match frame {
+                Frame::Packet(packet) => Ok(PyBytes::new(py, &packet).into_any()),
+                Frame::Error(code) => Ok(code.into_pyobject(py)?.into_any()),
+                Frame::QuickAck(token) => Ok(token.into_pyobject(py)?.into_any()),
+            })
+            .collect()
+    }
+}
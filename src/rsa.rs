@@ -1,9 +1,18 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
 use num_bigint::BigUint;
-use pyo3::prelude::*;
-use pyo3::types::PyBytes;
 
-/// Telegram server public keys
-struct PublicKey {
+use crate::aes::Ige256;
+use crate::error::{Error, Result};
+use crate::sha::{sha1, sha256};
+use crate::tl;
+
+/// RSA public key
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PublicKey {
     m: BigUint,
     e: BigUint,
 }
@@ -79,37 +88,279 @@ lazy_static::lazy_static! {
     };
 }
 
+/// Public keys of the test DCs, only used once `add_test_dc_keys` is called
+const TEST_DC_PUBLIC_KEYS: [&str; 1] = ["-----BEGIN RSA PUBLIC KEY-----
+MIIBCgKCAQEAyMEdY1aR+sCR3ZSJrtztKTKqigvO/vBfqACJLZtS7QMgCGXJ6XIR
+yy7mx66W0/sOFa7/1mAZtEoIokDP3ShoqF4fVNb6XeqgQfaUHd8wJpDWHcR2OFwv
+plUUI1PLTktZ9uW2WE23b+ixNwJjJGwBDJPQEQFBE+vfmH0JP503wr5INS1poWg/
+j25sIWeYPHYeOrFp/eXaqhISP6G+q2IeTaWTXpwZj4LzXq5YOpk4bYEQ6mvRq7D1
+aHWfYmlEGepfaYR8Q0YqvvhYtMte3ITnuSJs171+GDqpdKcSwHnd6FudwGO4pcCO
+j4WcDuXc2CTHgH8gFTNhp/Y8/SpDOhvn9QIDAQAB
+-----END RSA PUBLIC KEY-----"];
+
+lazy_static::lazy_static! {
+    /// Keys usable by the encryption functions, starting with the built-in server keys
+    static ref REGISTRY: RwLock<HashMap<i64, Arc<PublicKey>>> = RwLock::new(
+        SERVER_PUBLIC_KEYS.iter().map(|(&fingerprint, key)| (fingerprint, Arc::new(key.clone()))).collect()
+    );
+}
+
+/// Look up the registered public key with this fingerprint
+fn public_key(fingerprint: i64) -> Result<Arc<PublicKey>> {
+    REGISTRY
+        .read()
+        .unwrap()
+        .get(&fingerprint)
+        .cloned()
+        .ok_or(Error::UnknownFingerprint(fingerprint))
+}
+
+/// Register a PEM encoded public key and return its fingerprint
+pub fn add_public_key(pem: &str) -> Result<i64> {
+    let key = PublicKey::from_pem(pem)?;
+    let fingerprint = key.fingerprint();
+    REGISTRY.write().unwrap().insert(fingerprint, Arc::new(key));
+    Ok(fingerprint)
+}
+
+/// Registered public key with this fingerprint
+pub fn get_public_key(fingerprint: i64) -> Option<PublicKey> {
+    REGISTRY.read().unwrap().get(&fingerprint).map(|key| PublicKey::clone(key))
+}
+
+/// Telegram fingerprint of a PEM encoded public key
+pub fn rsa_fingerprint(pem: &str) -> Result<i64> {
+    Ok(PublicKey::from_pem(pem)?.fingerprint())
+}
+
+/// Unregister a public key, returning whether it was registered
+pub fn remove_public_key(fingerprint: i64) -> bool {
+    REGISTRY.write().unwrap().remove(&fingerprint).is_some()
+}
+
+/// Fingerprints of every registered public key, in ascending order
+pub fn list_public_keys() -> Vec<i64> {
+    let mut fingerprints: Vec<i64> = REGISTRY.read().unwrap().keys().copied().collect();
+    fingerprints.sort_unstable();
+    fingerprints
+}
+
+/// Register the test DC public keys and return their fingerprints
+pub fn add_test_dc_keys() -> Vec<i64> {
+    TEST_DC_PUBLIC_KEYS
+        .iter()
+        .map(|pem| add_public_key(pem).expect("built-in test DC key"))
+        .collect()
+}
+
+/// Read one DER element with the expected tag and advance past it
+fn der_element<'a>(data: &mut &'a [u8], tag: u8) -> Result<&'a [u8]> {
+    let (&found, rest) = data.split_first().ok_or(Error::InvalidPublicKey)?;
+    let (&first, mut rest) = rest.split_first().ok_or(Error::InvalidPublicKey)?;
+    if found != tag {
+        return Err(Error::InvalidPublicKey);
+    }
+
+    // Short form below 0x80, otherwise the number of big-endian length bytes
+    let len = match first {
+        0..=0x7f => first as usize,
+        0x81..=0x84 => {
+            let count = (first & 0x7f) as usize;
+            if rest.len() < count {
+                return Err(Error::InvalidPublicKey);
+            }
+            let len = rest[..count].iter().fold(0usize, |len, &b| len << 8 | b as usize);
+            rest = &rest[count..];
+            len
+        }
+        _ => return Err(Error::InvalidPublicKey),
+    };
+
+    if rest.len() < len {
+        return Err(Error::InvalidPublicKey);
+    }
+    let (element, rest) = rest.split_at(len);
+    *data = rest;
+    Ok(element)
+}
+
+/// DER encoded rsaEncryption object identifier (1.2.840.113549.1.1.1)
+const RSA_ENCRYPTION_OID: [u8; 9] = [0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x01];
+
+/// Base64 body of the PEM block with this label, if `pem` is one
+fn pem_body(pem: &str, label: &str) -> Option<Result<Vec<u8>>> {
+    let body = pem
+        .trim()
+        .strip_prefix(&format!("-----BEGIN {}-----", label))?
+        .strip_suffix(&format!("-----END {}-----", label))?;
+    let base64: String = body.chars().filter(|c| !c.is_ascii_whitespace()).collect();
+    Some(STANDARD.decode(base64).map_err(|_| Error::InvalidPublicKey))
+}
+
+impl PublicKey {
+    /// Parse a PKCS#1 `RSA PUBLIC KEY` or SPKI `PUBLIC KEY` PEM block
+    pub fn from_pem(pem: &str) -> Result<Self> {
+        if let Some(der) = pem_body(pem, "RSA PUBLIC KEY") {
+            return Self::from_pkcs1_der(&der?);
+        }
+        if let Some(der) = pem_body(pem, "PUBLIC KEY") {
+            return Self::from_spki_der(&der?);
+        }
+        Err(Error::InvalidPublicKey)
+    }
+
+    /// Parse a DER encoded PKCS#1 RSAPublicKey
+    pub fn from_pkcs1_der(der: &[u8]) -> Result<Self> {
+        // RSAPublicKey ::= SEQUENCE { modulus INTEGER, publicExponent INTEGER }
+        let mut data = der;
+        let mut sequence = der_element(&mut data, 0x30)?;
+        let m = BigUint::from_bytes_be(der_element(&mut sequence, 0x02)?);
+        let e = BigUint::from_bytes_be(der_element(&mut sequence, 0x02)?);
+        if !data.is_empty() || !sequence.is_empty() || m.bits() < 512 || e < BigUint::from(3u32) {
+            return Err(Error::InvalidPublicKey);
+        }
+        Ok(Self { m, e })
+    }
+
+    /// Parse a DER encoded SubjectPublicKeyInfo holding an RSA key
+    pub fn from_spki_der(der: &[u8]) -> Result<Self> {
+        // SEQUENCE { SEQUENCE { OID rsaEncryption, NULL }, BIT STRING { RSAPublicKey } }
+        let mut data = der;
+        let mut info = der_element(&mut data, 0x30)?;
+        let mut algorithm = der_element(&mut info, 0x30)?;
+        if der_element(&mut algorithm, 0x06)? != RSA_ENCRYPTION_OID {
+            return Err(Error::InvalidPublicKey);
+        }
+        let key = der_element(&mut info, 0x03)?;
+        if !data.is_empty() || !info.is_empty() {
+            return Err(Error::InvalidPublicKey);
+        }
+        // The first BIT STRING byte is the number of unused bits
+        match key.split_first() {
+            Some((0, key)) => Self::from_pkcs1_der(key),
+            _ => Err(Error::InvalidPublicKey),
+        }
+    }
+
+    /// Modulus n
+    pub fn modulus(&self) -> &BigUint {
+        &self.m
+    }
+
+    /// Public exponent e
+    pub fn exponent(&self) -> &BigUint {
+        &self.e
+    }
+
+    /// Lower 64 bits of SHA1 over the TL serialised modulus and exponent
+    pub fn fingerprint(&self) -> i64 {
+        let mut data = Vec::with_capacity(self.len() + 16);
+        tl::write_bytes(&mut data, &self.m.to_bytes_be());
+        tl::write_bytes(&mut data, &self.e.to_bytes_be());
+        i64::from_le_bytes(sha1(&data)[12..].try_into().unwrap())
+    }
+
+    /// Modulus size in bytes, which is also the ciphertext size
+    fn len(&self) -> usize {
+        self.m.bits().div_ceil(8) as usize
+    }
+
+    /// Raw RSA: data^e mod m, big-endian and left-padded to the modulus size
+    fn encrypt(&self, data: &BigUint) -> Result<Vec<u8>> {
+        if *data >= self.m {
+            return Err(Error::DataExceedsModulus);
+        }
+        let encrypted = data.modpow(&self.e, &self.m).to_bytes_be();
+        let mut result = vec![0u8; self.len()];
+        result[self.len() - encrypted.len()..].copy_from_slice(&encrypted);
+        Ok(result)
+    }
+}
+
+/// Padding applied by `rsa_encrypt_padded` before the RSA operation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Padding {
+    /// Encrypt the data as is
+    #[default]
+    None,
+    /// Legacy handshake padding: SHA1(data) + data + random bytes up to one byte short of the modulus
+    Sha1,
+}
+
 /// RSA encrypt using Telegram server public key
-#[pyfunction]
-#[pyo3(signature = (data, fingerprint, /))]
-pub fn rsa_encrypt<'py>(py: Python<'py>, data: &[u8], fingerprint: i64) -> PyResult<Bound<'py, PyBytes>> {
-    let result = py.detach(|| {
-        let pubkey = SERVER_PUBLIC_KEYS.get(&fingerprint).ok_or_else(|| {
-            PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
-                "Unknown fingerprint: {}",
-                fingerprint
-            ))
-        })?;
-
-        // Convert data to BigUint
-        let data_int = BigUint::from_bytes_be(data);
-
-        // RSA encryption: ciphertext = plaintext^e mod m
-        let encrypted = data_int.modpow(&pubkey.e, &pubkey.m);
-
-        // Convert to 256-byte big-endian representation
-        let mut result = encrypted.to_bytes_be();
-
-        // Pad to 256 bytes if necessary
-        if result.len() < 256 {
-            let mut padded = vec![0u8; 256];
-            let offset = 256 - result.len();
-            padded[offset..].copy_from_slice(&result);
-            result = padded;
+/// The data is taken as a big-endian integer and must be below the modulus
+pub fn rsa_encrypt(data: &[u8], fingerprint: i64) -> Result<Vec<u8>> {
+    rsa_encrypt_padded(data, fingerprint, Padding::None)
+}
+
+/// RSA encrypt using Telegram server public key after applying `padding`
+pub fn rsa_encrypt_padded(data: &[u8], fingerprint: i64, padding: Padding) -> Result<Vec<u8>> {
+    let pubkey = public_key(fingerprint)?;
+
+    match padding {
+        Padding::None => {
+            if data.len() > pubkey.len() {
+                return Err(Error::DataTooLong { max: pubkey.len(), actual: data.len() });
+            }
+            pubkey.encrypt(&BigUint::from_bytes_be(data))
+        }
+        Padding::Sha1 => {
+            // 255 bytes for the 2048-bit server keys
+            let block_len = pubkey.len() - 1;
+            if data.len() + 20 > block_len {
+                return Err(Error::DataTooLong { max: block_len.saturating_sub(20), actual: data.len() });
+            }
+
+            let mut block = vec![0u8; block_len];
+            block[..20].copy_from_slice(&sha1(data));
+            block[20..20 + data.len()].copy_from_slice(data);
+            rand::fill(&mut block[20 + data.len()..]);
+            pubkey.encrypt(&BigUint::from_bytes_be(&block))
         }
+    }
+}
 
-        Ok::<Vec<u8>, PyErr>(result)
-    })?;
+/// Largest payload RSA_PAD can carry
+pub const RSA_PAD_MAX_DATA: usize = 144;
 
-    Ok(PyBytes::new(py, &result))
+/// RSA_PAD encryption used by req_DH_params
+/// The data is padded to 192 bytes, reversed, hashed with a random temp_key and
+/// AES-256-IGE encrypted under it; temp_key is then masked with a SHA-256 of the result.
+/// A new temp_key is drawn until the 256-byte block is below the modulus
+pub fn rsa_pad_encrypt(data: &[u8], fingerprint: i64) -> Result<Vec<u8>> {
+    let pubkey = public_key(fingerprint)?;
+    if pubkey.len() != 256 {
+        return Err(Error::UnsupportedKeySize(pubkey.len() * 8));
+    }
+    if data.len() > RSA_PAD_MAX_DATA {
+        return Err(Error::DataTooLong { max: RSA_PAD_MAX_DATA, actual: data.len() });
+    }
+
+    let mut data_with_padding = [0u8; 192];
+    data_with_padding[..data.len()].copy_from_slice(data);
+    rand::fill(&mut data_with_padding[data.len()..]);
+    let mut data_pad_reversed = data_with_padding;
+    data_pad_reversed.reverse();
+
+    loop {
+        let mut temp_key = [0u8; 32];
+        rand::fill(&mut temp_key[..]);
+
+        // data_with_hash = data_pad_reversed + SHA256(temp_key + data_with_padding)
+        let mut key_aes_encrypted = [0u8; 256];
+        let aes_encrypted = &mut key_aes_encrypted[32..];
+        aes_encrypted[..192].copy_from_slice(&data_pad_reversed);
+        aes_encrypted[192..].copy_from_slice(&sha256(&[&temp_key[..], &data_with_padding].concat()));
+        Ige256::new(&temp_key)?.encrypt(aes_encrypted, &[0u8; 32])?;
+
+        let hash = sha256(aes_encrypted);
+        for (byte, (k, h)) in key_aes_encrypted[..32].iter_mut().zip(temp_key.iter().zip(hash)) {
+            *byte = k ^ h;
+        }
+
+        let value = BigUint::from_bytes_be(&key_aes_encrypted);
+        if value < pubkey.m {
+            return pubkey.encrypt(&value);
+        }
+    }
 }
@@ -0,0 +1,109 @@
+//! Just enough TL serialisation for the handshake objects.
+
+use crate::error::{Error, Result};
+
+/// Serialise a TL `bytes`/`string`: length prefix, data, then zero padding to a multiple of 4
+pub(crate) fn write_bytes(out: &mut Vec<u8>, data: &[u8]) {
+    let header = if data.len() < 254 {
+        out.push(data.len() as u8);
+        1
+    } else {
+        out.push(254);
+        out.extend_from_slice(&(data.len() as u32).to_le_bytes()[..3]);
+        4
+    };
+    out.extend_from_slice(data);
+    let padding = (header + data.len()).next_multiple_of(4) - header - data.len();
+    out.resize(out.len() + padding, 0);
+}
+
+/// Cursor over a serialized TL object, every read fails with `MalformedTl` past the end
+pub(crate) struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.data.len() < len {
+            return Err(Error::MalformedTl);
+        }
+        let (head, tail) = self.data.split_at(len);
+        self.data = tail;
+        Ok(head)
+    }
+
+    pub(crate) fn array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        Ok(self.take(N)?.try_into().unwrap())
+    }
+
+    pub(crate) fn u32(&mut self) -> Result<u32> {
+        self.array().map(u32::from_le_bytes)
+    }
+
+    pub(crate) fn i32(&mut self) -> Result<i32> {
+        self.array().map(i32::from_le_bytes)
+    }
+
+    pub(crate) fn i64(&mut self) -> Result<i64> {
+        self.array().map(i64::from_le_bytes)
+    }
+
+    pub(crate) fn bytes(&mut self) -> Result<&'a [u8]> {
+        let (header, len) = match self.take(1)?[0] {
+            254 => {
+                let len = self.take(3)?;
+                (4, u32::from_le_bytes([len[0], len[1], len[2], 0]) as usize)
+            }
+            255 => return Err(Error::MalformedTl),
+            len => (1, len as usize),
+        };
+        let data = self.take(len)?;
+        self.take((header + len).next_multiple_of(4) - header - len)?;
+        Ok(data)
+    }
+
+    /// Bare `Vector<long>` preceded by the vector constructor
+    pub(crate) fn vector_i64(&mut self) -> Result<Vec<i64>> {
+        let constructor = self.u32()?;
+        if constructor != VECTOR {
+            return Err(Error::UnexpectedConstructor(constructor));
+        }
+        let count = self.u32()? as usize;
+        if count > self.data.len() / 8 {
+            return Err(Error::MalformedTl);
+        }
+        (0..count).map(|_| self.i64()).collect()
+    }
+}
+
+/// vector#1cb5c415
+pub(crate) const VECTOR: u32 = 0x1cb5c415;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_bytes_pads_by_its_own_length() {
+        for prefix in 0..4 {
+            for len in [0usize, 1, 3, 4, 5, 253, 254, 255, 256] {
+                let data: Vec<u8> = (0..len).map(|i| i as u8).collect();
+                let mut out = vec![0xaa; prefix];
+                write_bytes(&mut out, &data);
+
+                let header = if len < 254 { 1 } else { 4 };
+                let written = &out[prefix..];
+                assert_eq!(written.len(), (header + len).next_multiple_of(4), "prefix {prefix}, len {len}");
+                assert!(written[header + len..].iter().all(|&b| b == 0));
+
+                let mut reader = Reader::new(written);
+                assert_eq!(reader.bytes().unwrap(), data);
+                assert!(reader.data.is_empty());
+            }
+        }
+    }
+}
@@ -0,0 +1,245 @@
+use crate::error::{Error, Result};
+use crate::obfuscated::{ABRIDGED, INTERMEDIATE, PADDED_INTERMEDIATE};
+
+/// Largest packet accepted by the decoders
+pub const MAX_PACKET_LEN: usize = 1 << 24;
+
+/// High bit of the transport length, used to request and to flag quick ACKs
+const QUICK_ACK_FLAG: u32 = 1 << 31;
+
+fn flag(quick_ack: bool) -> u32 {
+    if quick_ack {
+        QUICK_ACK_FLAG
+    } else {
+        0
+    }
+}
+
+/// MTProto TCP transport
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transport {
+    /// 1- or 4-byte length in 4-byte units
+    Abridged,
+    /// 4-byte length in bytes
+    Intermediate,
+    /// Intermediate with 0..=15 random padding bytes
+    PaddedIntermediate,
+    /// 4-byte length, seq_no and a CRC32 trailer
+    Full,
+}
+
+impl Transport {
+    /// Transport identified by an obfuscated2 protocol tag
+    pub fn from_tag(tag: &[u8]) -> Result<Self> {
+        match tag {
+            t if t == ABRIDGED => Ok(Transport::Abridged),
+            t if t == INTERMEDIATE => Ok(Transport::Intermediate),
+            t if t == PADDED_INTERMEDIATE => Ok(Transport::PaddedIntermediate),
+            _ => Err(Error::InvalidProtocolTag),
+        }
+    }
+
+    /// Obfuscated2 protocol tag, the full transport has none
+    pub fn tag(&self) -> Option<[u8; 4]> {
+        match self {
+            Transport::Abridged => Some(ABRIDGED),
+            Transport::Intermediate => Some(INTERMEDIATE),
+            Transport::PaddedIntermediate => Some(PADDED_INTERMEDIATE),
+            Transport::Full => None,
+        }
+    }
+
+    /// Bytes a client sends first on a plain TCP connection
+    pub fn header(&self) -> &'static [u8] {
+        match self {
+            Transport::Abridged => &ABRIDGED[..1],
+            Transport::Intermediate => &INTERMEDIATE,
+            Transport::PaddedIntermediate => &PADDED_INTERMEDIATE,
+            Transport::Full => &[],
+        }
+    }
+}
+
+/// Frame received from the server
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Frame {
+    /// MTProto packet, padding included for the padded intermediate transport
+    Packet(Vec<u8>),
+    /// Negative transport error code such as -404
+    Error(i32),
+    /// Quick-ACK token with its most significant bit set
+    QuickAck(u32),
+}
+
+/// Sans-IO framing for one direction pair of a transport connection
+/// `encode` frames outgoing packets, `feed` buffers received bytes and returns complete frames
+#[derive(Debug, Clone)]
+pub struct Codec {
+    transport: Transport,
+    buffer: Vec<u8>,
+    send_seq: u32,
+    recv_seq: u32,
+}
+
+impl Codec {
+    pub fn new(transport: Transport) -> Self {
+        Self {
+            transport,
+            buffer: Vec::new(),
+            send_seq: 0,
+            recv_seq: 0,
+        }
+    }
+
+    pub fn transport(&self) -> Transport {
+        self.transport
+    }
+
+    /// Frame an outgoing packet
+    pub fn encode(&mut self, packet: &[u8]) -> Result<Vec<u8>> {
+        self.frame(packet, false)
+    }
+
+    /// Frame an outgoing packet with the length's high bit set to request a quick ACK
+    pub fn encode_quick_ack(&mut self, packet: &[u8]) -> Result<Vec<u8>> {
+        self.frame(packet, true)
+    }
+
+    fn frame(&mut self, packet: &[u8], quick_ack: bool) -> Result<Vec<u8>> {
+        if packet.len() > MAX_PACKET_LEN {
+            return Err(Error::PacketTooLarge(packet.len()));
+        }
+
+        let mut out = Vec::with_capacity(packet.len() + 28);
+        match self.transport {
+            Transport::Abridged => {
+                if !packet.len().is_multiple_of(4) {
+                    return Err(Error::UnalignedPacket(packet.len()));
+                }
+                let len = packet.len() / 4;
+                if len < 0x7f {
+                    out.push(len as u8);
+                } else {
+                    out.push(0x7f);
+                    out.extend_from_slice(&(len as u32).to_le_bytes()[..3]);
+                }
+                if quick_ack {
+                    out[0] |= 0x80;
+                }
+                out.extend_from_slice(packet);
+            }
+            Transport::Intermediate => {
+                out.extend_from_slice(&(packet.len() as u32 | flag(quick_ack)).to_le_bytes());
+                out.extend_from_slice(packet);
+            }
+            Transport::PaddedIntermediate => {
+                let mut padding = [0u8; 16];
+                rand::fill(&mut padding[..]);
+                let padding = &padding[1..1 + (padding[0] % 16) as usize];
+
+                let len = (packet.len() + padding.len()) as u32 | flag(quick_ack);
+                out.extend_from_slice(&len.to_le_bytes());
+                out.extend_from_slice(packet);
+                out.extend_from_slice(padding);
+            }
+            Transport::Full => {
+                // len (4) + seq_no (4) + packet + crc32 (4)
+                out.extend_from_slice(&((packet.len() + 12) as u32 | flag(quick_ack)).to_le_bytes());
+                out.extend_from_slice(&self.send_seq.to_le_bytes());
+                out.extend_from_slice(packet);
+                out.extend_from_slice(&crc32fast::hash(&out).to_le_bytes());
+                self.send_seq = self.send_seq.wrapping_add(1);
+            }
+        }
+        Ok(out)
+    }
+
+    /// Feed received bytes and return every complete frame
+    /// On error the buffered data is left as is, the connection should be dropped
+    pub fn feed(&mut self, data: &[u8]) -> Result<Vec<Frame>> {
+        self.buffer.extend_from_slice(data);
+
+        let mut frames = Vec::new();
+        let mut pos = 0;
+        loop {
+            if let Some(token) = self.quick_ack(&self.buffer[pos..]) {
+                frames.push(Frame::QuickAck(token));
+                pos += 4;
+                continue;
+            }
+            let Some((header, len)) = self.frame_len(&self.buffer[pos..])? else {
+                break;
+            };
+            let frame = &self.buffer[pos..pos + header + len];
+            let payload = match self.transport {
+                Transport::Full => {
+                    let (body, crc) = frame.split_at(frame.len() - 4);
+                    if crc32fast::hash(body).to_le_bytes() != crc {
+                        return Err(Error::BadCrc);
+                    }
+                    let seq_no = u32::from_le_bytes(body[4..8].try_into().unwrap());
+                    if seq_no != self.recv_seq {
+                        return Err(Error::BadSeqNo(seq_no));
+                    }
+                    self.recv_seq = self.recv_seq.wrapping_add(1);
+                    &body[8..]
+                }
+                _ => &frame[header..],
+            };
+            frames.push(Self::to_frame(payload));
+            pos += header + len;
+        }
+        self.buffer.drain(..pos);
+        Ok(frames)
+    }
+
+    /// Quick-ACK token at the start of `data`, flagged by the high bit of the length
+    /// Abridged sends it big-endian so that the flag lands in the first byte
+    fn quick_ack(&self, data: &[u8]) -> Option<u32> {
+        let bytes: [u8; 4] = data.get(..4)?.try_into().unwrap();
+        let token = match self.transport {
+            Transport::Abridged => u32::from_be_bytes(bytes),
+            _ => u32::from_le_bytes(bytes),
+        };
+        (token & QUICK_ACK_FLAG != 0).then_some(token)
+    }
+
+    /// (header length, body length) of the frame at the start of `data`, once it is complete
+    fn frame_len(&self, data: &[u8]) -> Result<Option<(usize, usize)>> {
+        let (header, len) = match self.transport {
+            Transport::Abridged => match data.first() {
+                None => return Ok(None),
+                Some(&0x7f) if data.len() < 4 => return Ok(None),
+                // Quick ACK whose 4 bytes have not all arrived yet
+                Some(&len) if len & 0x80 != 0 => return Ok(None),
+                Some(&0x7f) => (4, u32::from_le_bytes([data[1], data[2], data[3], 0]) as usize * 4),
+                Some(&len) => (1, len as usize * 4),
+            },
+            _ if data.len() < 4 => return Ok(None),
+            _ => (4, u32::from_le_bytes(data[..4].try_into().unwrap()) as usize),
+        };
+
+        if len > MAX_PACKET_LEN {
+            return Err(Error::PacketTooLarge(len));
+        }
+        // The full transport length covers itself, seq_no and the CRC32
+        let (header, len) = match self.transport {
+            Transport::Full if len < 12 => return Err(Error::InvalidPacketLength(len)),
+            Transport::Full => (0, len),
+            _ => (header, len),
+        };
+
+        Ok((data.len() >= header + len).then_some((header, len)))
+    }
+
+    /// Error codes are sent as a lone negative 32-bit integer
+    fn to_frame(payload: &[u8]) -> Frame {
+        if let Ok(code) = <[u8; 4]>::try_from(payload) {
+            let code = i32::from_le_bytes(code);
+            if code < 0 {
+                return Frame::Error(code);
+            }
+        }
+        Frame::Packet(payload.to_vec())
+    }
+}
@@ -107,6 +107,37 @@ class TestCTR256Cryptography(unittest.TestCase):
         self.assertEqual(tgcrypto.ctr256_encrypt(plaintext, key, iv, bytes(1)), ciphertext)
 
 
+class TestCtrStream(unittest.TestCase):
+    def test_matches_functions(self):
+        key, iv = os.urandom(32), os.urandom(16)
+        data = os.urandom(100)
+        stream = tgcrypto.CtrStream(key, iv)
+
+        encrypted = stream.process(data[:7]) + stream.process(data[7:])
+        self.assertEqual(encrypted, tgcrypto.ctr256_encrypt(data, key, iv, bytes(1)))
+        self.assertEqual(stream.tell(), 100)
+
+    def test_seek(self):
+        key, iv = os.urandom(32), os.urandom(16)
+        data = os.urandom(100)
+        encrypted = tgcrypto.CtrStream(key, iv).process(data)
+
+        stream = tgcrypto.CtrStream(key, iv, 33)
+        self.assertEqual(stream.process(data[33:]), encrypted[33:])
+
+        stream.seek(10)
+        self.assertEqual(stream.tell(), 10)
+        self.assertEqual(stream.process(data[10:50]), encrypted[10:50])
+
+    def test_invalid_offset(self):
+        with self.assertRaises(OverflowError):
+            tgcrypto.CtrStream(os.urandom(32), os.urandom(16), -1)
+
+    def test_invalid_iv_size(self):
+        with self.assertRaisesRegex(ValueError, r"IV must be 16 bytes"):
+            tgcrypto.CtrStream(os.urandom(32), os.urandom(15))
+
+
 class TestCTR256Input(unittest.TestCase):
     TYPE_ERROR_PATTERN = r"'\w+' does not (support|have) the buffer interface|a bytes-like object is required, not '\w+'"
 
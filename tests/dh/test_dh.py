@@ -0,0 +1,79 @@
+#  Pyrogram - Telegram MTProto API Client Library for Python
+#  Copyright (C) 2017-present Dan <https://github.com/delivrance>
+#
+#  This file is part of Pyrogram.
+#
+#  Pyrogram is free software: you can redistribute it and/or modify
+#  it under the terms of the GNU Lesser General Public License as published
+#  by the Free Software Foundation, either version 3 of the License, or
+#  (at your option) any later version.
+#
+#  Pyrogram is distributed in the hope that it will be useful,
+#  but WITHOUT ANY WARRANTY; without even the implied warranty of
+#  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+#  GNU Lesser General Public License for more details.
+#
+#  You should have received a copy of the GNU Lesser General Public License
+#  along with Pyrogram.  If not, see <http://www.gnu.org/licenses/>.
+
+import os
+import unittest
+
+import tgcrypto
+
+DH_PRIME = int(
+    "C71CAEB9C6B1C9048E6C522F70F13F73980D40238E3E21C14934D037563D930F48198A0AA7C14058229493D22530F4DBFA336F6E0AC925139543AED44CCE7C3720FD51F69458705AC68CD4FE6B6B13ABDC9746512969328454F18FAF8C595F642477FE96BB2A941D5BCD1D4AC8CC49880708FA9B378E3C4F3A9060BEE67CF9A4A4A695811051907E162753B56B0F6B410DBA74D8A84B2A14B3144E0EF1284754FD17ED950D5965B4B9DD46582DB1178D169C6BC465B0D6FF9CA3928FEF5B9AE4E418FC15E83EBEA0F87FA9FF5EED70050DED2849F47BF959D956850CE929851F0D8115F635B105EE2E4E15D04B2454BF6F4FADF034B10403119CD8E3B92FCC5B",
+    16,
+)
+DH_PRIME_BYTES = DH_PRIME.to_bytes(256, "big")
+
+
+class TestDHParams(unittest.TestCase):
+    def test_telegram_prime(self):
+        tgcrypto.check_dh_prime(DH_PRIME_BYTES)
+        tgcrypto.check_dh_params(DH_PRIME_BYTES, 3)
+
+    def test_invalid_prime(self):
+        with self.assertRaisesRegex(ValueError, "safe prime"):
+            tgcrypto.check_dh_prime((DH_PRIME + 2).to_bytes(256, "big"))
+
+        with self.assertRaisesRegex(ValueError, "safe prime"):
+            tgcrypto.check_dh_prime(DH_PRIME_BYTES[1:])
+
+    def test_invalid_generator(self):
+        for g in (0, 1, 2, 8):
+            with self.assertRaisesRegex(ValueError, "generator"):
+                tgcrypto.check_dh_params(DH_PRIME_BYTES, g)
+
+    def test_g_a_range(self):
+        tgcrypto.check_g_a(pow(3, 12345678901234567890, DH_PRIME).to_bytes(256, "big"), DH_PRIME_BYTES)
+
+        for g_a in (2, 2 ** 1984 - 1, DH_PRIME - 2 ** 1984 + 1, DH_PRIME - 1):
+            with self.assertRaisesRegex(ValueError, "safe range"):
+                tgcrypto.check_g_a(g_a.to_bytes(256, "big"), DH_PRIME_BYTES)
+
+
+class TestDHExchange(unittest.TestCase):
+    def test_compute_g_b(self):
+        b = os.urandom(256)
+        g_b = tgcrypto.compute_g_b(3, b, DH_PRIME_BYTES)
+
+        self.assertEqual(len(g_b), 256)
+        self.assertEqual(int.from_bytes(g_b, "big"), pow(3, int.from_bytes(b, "big"), DH_PRIME))
+
+    def test_compute_auth_key(self):
+        a, b = os.urandom(256), os.urandom(256)
+        g_a = tgcrypto.compute_g_b(3, a, DH_PRIME_BYTES)
+        g_b = tgcrypto.compute_g_b(3, b, DH_PRIME_BYTES)
+        auth_key = tgcrypto.compute_auth_key(g_a, b, DH_PRIME_BYTES)
+
+        self.assertEqual(auth_key, tgcrypto.compute_auth_key(g_b, a, DH_PRIME_BYTES))
+        self.assertEqual(int.from_bytes(auth_key, "big"), pow(int.from_bytes(g_a, "big"), int.from_bytes(b, "big"), DH_PRIME))
+
+    def test_auth_key_rejects_small_g_a(self):
+        with self.assertRaisesRegex(ValueError, "safe range"):
+            tgcrypto.compute_auth_key((1).to_bytes(256, "big"), os.urandom(256), DH_PRIME_BYTES)
+
+
+if __name__ == "__main__":
+    unittest.main()
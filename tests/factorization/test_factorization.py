@@ -0,0 +1,142 @@
+#  Pyrogram - Telegram MTProto API Client Library for Python
+#  Copyright (C) 2017-present Dan <https://github.com/delivrance>
+#
+#  This file is part of Pyrogram.
+#
+#  Pyrogram is free software: you can redistribute it and/or modify
+#  it under the terms of the GNU Lesser General Public License as published
+#  by the Free Software Foundation, either version 3 of the License, or
+#  (at your option) any later version.
+#
+#  Pyrogram is distributed in the hope that it will be useful,
+#  but WITHOUT ANY WARRANTY; without even the implied warranty of
+#  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+#  GNU Lesser General Public License for more details.
+#
+#  You should have received a copy of the GNU Lesser General Public License
+#  along with Pyrogram.  If not, see <http://www.gnu.org/licenses/>.
+
+import unittest
+
+import tgcrypto
+
+
+class TestFactorize(unittest.TestCase):
+    def test_small(self):
+        self.assertEqual(tgcrypto.factorize(15), (3, 5))
+
+    def test_res_pq(self):
+        pq = 0x17ED48941A08F981
+        self.assertEqual(tgcrypto.factorize(pq), (0x494C553B, 0x53911073))
+        self.assertEqual(tgcrypto.factorize(pq.to_bytes(8, "big")), (0x494C553B, 0x53911073))
+
+    def test_large(self):
+        large_prime = 2 ** 127 - 1
+        self.assertEqual(tgcrypto.factorize(large_prime * 1000003), (1000003, large_prime))
+
+    def test_no_factor(self):
+        for pq in (0, 1, 3, 1000003, b""):
+            with self.assertRaisesRegex(ValueError, "Could not factorize"):
+                tgcrypto.factorize(pq)
+
+    def test_negative(self):
+        with self.assertRaisesRegex(ValueError, "positive"):
+            tgcrypto.factorize(-15)
+
+    def test_invalid_type(self):
+        with self.assertRaises(TypeError):
+            tgcrypto.factorize("15")
+
+
+class TestFactorizeIterations(unittest.TestCase):
+    PQ = 0x17ED48941A08F981
+
+    def test_seed(self):
+        first = tgcrypto.factorize_with_iterations(self.PQ, seed=42)
+        self.assertEqual(first[:2], (0x494C553B, 0x53911073))
+        self.assertGreater(first[2], 0)
+        self.assertEqual(first[3], 42)
+        self.assertEqual(tgcrypto.factorize_with_iterations(self.PQ, seed=42), first)
+        self.assertEqual(tgcrypto.factorize(self.PQ, seed=42), first[:2])
+
+    def test_random_seed_replays(self):
+        first = tgcrypto.factorize_with_iterations(self.PQ)
+        self.assertEqual(tgcrypto.factorize_with_iterations(self.PQ, seed=first[3]), first)
+
+    def test_max_iterations(self):
+        iterations = tgcrypto.factorize_with_iterations(self.PQ, seed=7)[2]
+        self.assertEqual(tgcrypto.factorize_with_iterations(self.PQ, seed=7, max_iterations=iterations)[2], iterations)
+
+        with self.assertRaises(tgcrypto.IterationLimitError):
+            tgcrypto.factorize(self.PQ, seed=7, max_iterations=iterations - 1)
+
+        # Still a ValueError for existing callers
+        pq = (2 ** 61 - 1) * (2 ** 89 - 1)
+        with self.assertRaisesRegex(ValueError, "after 1000 iterations") as context:
+            tgcrypto.factorize(pq, max_iterations=1000)
+
+        # The drawn seed replays the failed run
+        error = context.exception
+        self.assertEqual(error.iterations, 1000)
+        self.assertIn(f"(seed {error.seed})", str(error))
+        with self.assertRaises(tgcrypto.IterationLimitError) as context:
+            tgcrypto.factorize(pq, seed=error.seed, max_iterations=1000)
+        self.assertEqual((context.exception.iterations, context.exception.seed), (1000, error.seed))
+
+    def test_prime_without_limit(self):
+        with self.assertRaises(ValueError) as context:
+            tgcrypto.factorize(1000003)
+        self.assertNotIsInstance(context.exception, tgcrypto.IterationLimitError)
+
+
+class TestIsPrime(unittest.TestCase):
+    def test_small(self):
+        primes = [n for n in range(2, 1000) if all(n % d for d in range(2, int(n ** 0.5) + 1))]
+        self.assertEqual([n for n in range(1000) if tgcrypto.is_prime(n)], primes)
+
+    def test_large(self):
+        for k in (61, 89, 127, 521):
+            self.assertTrue(tgcrypto.is_prime(2 ** k - 1))
+        self.assertTrue(tgcrypto.is_prime((2 ** 127 - 1).to_bytes(16, "big")))
+        self.assertFalse(tgcrypto.is_prime((2 ** 61 - 1) * (2 ** 89 - 1)))
+        self.assertFalse(tgcrypto.is_prime((2 ** 127 - 1) ** 2))
+
+    def test_pseudoprimes(self):
+        for n in (2047, 3215031751, 3825123056546413051, 318665857834031151167461, 3317044064679887385961981, 5777):
+            self.assertFalse(tgcrypto.is_prime(n))
+
+    def test_negative(self):
+        with self.assertRaisesRegex(ValueError, "n must be positive"):
+            tgcrypto.is_prime(-7)
+
+
+class TestFactorAll(unittest.TestCase):
+    def test_small(self):
+        self.assertEqual(tgcrypto.factor_all(1), [])
+        self.assertEqual(tgcrypto.factor_all(360), [(2, 3), (3, 2), (5, 1)])
+        self.assertEqual(tgcrypto.factor_all(b"\x01\x68"), [(2, 3), (3, 2), (5, 1)])
+
+    def test_large(self):
+        n = 1009 ** 5 * (2 ** 61 - 1) ** 3 * 0x17ED48941A08F981
+        self.assertEqual(
+            tgcrypto.factor_all(n), [(1009, 5), (0x494C553B, 1), (0x53911073, 1), (2 ** 61 - 1, 3)]
+        )
+
+    def test_max_iterations(self):
+        n = 360 * (2 ** 61 - 1) * (2 ** 89 - 1)
+        with self.assertRaises(tgcrypto.IterationLimitError) as context:
+            tgcrypto.factor_all(n, seed=3, max_iterations=1000)
+        self.assertEqual((context.exception.iterations, context.exception.seed), (1000, 3))
+
+        self.assertEqual(tgcrypto.factor_all(360 * (2 ** 127 - 1), max_iterations=0), [(2, 3), (3, 2), (5, 1), (2 ** 127 - 1, 1)])
+        self.assertEqual(tgcrypto.factor_all(0x17ED48941A08F981, seed=3), [(0x494C553B, 1), (0x53911073, 1)])
+
+    def test_invalid(self):
+        with self.assertRaisesRegex(ValueError, "Could not factorize"):
+            tgcrypto.factor_all(0)
+        with self.assertRaisesRegex(ValueError, "n must be positive"):
+            tgcrypto.factor_all(-4)
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,187 @@
+#  Pyrogram - Telegram MTProto API Client Library for Python
+#  Copyright (C) 2017-present Dan <https://github.com/delivrance>
+#
+#  This file is part of Pyrogram.
+#
+#  Pyrogram is free software: you can redistribute it and/or modify
+#  it under the terms of the GNU Lesser General Public License as published
+#  by the Free Software Foundation, either version 3 of the License, or
+#  (at your option) any later version.
+#
+#  Pyrogram is distributed in the hope that it will be useful,
+#  but WITHOUT ANY WARRANTY; without even the implied warranty of
+#  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+#  GNU Lesser General Public License for more details.
+#
+#  You should have received a copy of the GNU Lesser General Public License
+#  along with Pyrogram.  If not, see <http://www.gnu.org/licenses/>.
+
+import hashlib
+import os
+import struct
+import unittest
+
+import tgcrypto
+
+FIXTURES = os.path.join(os.path.dirname(__file__), os.pardir, "fixtures")
+
+with open(os.path.join(FIXTURES, "rsa_test_key.pem")) as f:
+    TEST_KEY_PEM = f.read()
+
+with open(os.path.join(FIXTURES, "rsa_test_key.hex")) as f:
+    TEST_KEY_N, TEST_KEY_D = (int(line, 16) for line in f.read().split())
+
+DH_PRIME = int(
+    "C71CAEB9C6B1C9048E6C522F70F13F73980D40238E3E21C14934D037563D930F48198A0AA7C14058229493D22530F4DBFA336F6E0AC925139543AED44CCE7C3720FD51F69458705AC68CD4FE6B6B13ABDC9746512969328454F18FAF8C595F642477FE96BB2A941D5BCD1D4AC8CC49880708FA9B378E3C4F3A9060BEE67CF9A4A4A695811051907E162753B56B0F6B410DBA74D8A84B2A14B3144E0EF1284754FD17ED950D5965B4B9DD46582DB1178D169C6BC465B0D6FF9CA3928FEF5B9AE4E418FC15E83EBEA0F87FA9FF5EED70050DED2849F47BF959D956850CE929851F0D8115F635B105EE2E4E15D04B2454BF6F4FADF034B10403119CD8E3B92FCC5B",
+    16,
+)
+
+
+def tl_bytes(data: bytes) -> bytes:
+    header = bytes([len(data)]) if len(data) < 254 else b"\xfe" + len(data).to_bytes(3, "little")
+    padding = -(len(header) + len(data)) % 4
+    return header + data + bytes(padding)
+
+
+class Reader:
+    def __init__(self, data: bytes):
+        self.data = data
+
+    def take(self, n: int) -> bytes:
+        head, self.data = self.data[:n], self.data[n:]
+        return head
+
+    def int(self) -> int:
+        return int.from_bytes(self.take(4), "little")
+
+    def bytes(self) -> bytes:
+        if self.data[0] == 254:
+            length, header = int.from_bytes(self.take(4)[1:], "little"), 4
+        else:
+            length, header = self.take(1)[0], 1
+        data = self.take(length)
+        self.take(-(header + length) % 4)
+        return data
+
+
+def plain(body: bytes) -> bytes:
+    return bytes(8) + struct.pack("<qI", 1, len(body)) + body
+
+
+def rsa_pad_decrypt(encrypted: bytes) -> bytes:
+    decrypted = pow(int.from_bytes(encrypted, "big"), TEST_KEY_D, TEST_KEY_N).to_bytes(256, "big")
+    temp_key_xor, aes_encrypted = decrypted[:32], decrypted[32:]
+    temp_key = bytes(a ^ b for a, b in zip(temp_key_xor, hashlib.sha256(aes_encrypted).digest()))
+    data_with_hash = tgcrypto.ige256_decrypt(aes_encrypted, temp_key, bytes(32))
+    return data_with_hash[:192][::-1]
+
+
+class Server:
+    """Server side of the handshake for the test RSA key"""
+
+    def __init__(self, fingerprint: int):
+        self.fingerprint = fingerprint
+        self.server_nonce = os.urandom(16)
+        self.a = os.urandom(256)
+        self.retries = 0
+
+    def res_pq(self, packet: bytes) -> bytes:
+        reader = Reader(packet[20:])
+        assert reader.int() == 0xbe7e8ef1
+        self.nonce = reader.take(16)
+        return plain(
+            struct.pack("<I", 0x05162463) + self.nonce + self.server_nonce
+            + tl_bytes((0x17ED48941A08F981).to_bytes(8, "big"))
+            + struct.pack("<IIqq", 0x1cb5c415, 2, 1, self.fingerprint)
+        )
+
+    def server_dh_params(self, packet: bytes, expected_constructor: int) -> bytes:
+        reader = Reader(packet[20:])
+        assert reader.int() == 0xd712e4be
+        reader.take(32)
+        assert reader.bytes() == (0x494C553B).to_bytes(4, "big")
+        assert reader.bytes() == (0x53911073).to_bytes(4, "big")
+        reader.take(8)
+        inner = Reader(rsa_pad_decrypt(reader.bytes()))
+        assert inner.int() == expected_constructor
+        inner.bytes(), inner.bytes(), inner.bytes()
+        assert inner.take(32) == self.nonce + self.server_nonce
+        self.new_nonce = inner.take(32)
+
+        g_a = tgcrypto.compute_g_b(3, self.a, DH_PRIME.to_bytes(256, "big"))
+        data = (
+            struct.pack("<I", 0xb5890dba) + self.nonce + self.server_nonce + struct.pack("<I", 3)
+            + tl_bytes(DH_PRIME.to_bytes(256, "big")) + tl_bytes(g_a) + struct.pack("<I", 1700000000)
+        )
+        answer = hashlib.sha1(data).digest() + data
+        answer += os.urandom(-len(answer) % 16)
+        answer = tgcrypto.ige256_encrypt(answer, *tgcrypto.tmp_aes_key_iv(self.new_nonce, self.server_nonce))
+        return plain(struct.pack("<I", 0xd0e8075c) + self.nonce + self.server_nonce + tl_bytes(answer))
+
+    def dh_gen(self, packet: bytes, retry: bool) -> bytes:
+        reader = Reader(packet[20:])
+        assert reader.int() == 0xf5045f1f
+        assert reader.take(32) == self.nonce + self.server_nonce
+        data = tgcrypto.decrypt_server_dh_inner_data(reader.bytes(), self.new_nonce, self.server_nonce)
+
+        inner = Reader(data)
+        assert inner.int() == 0x6643b654
+        inner.take(40)
+        g_b = int.from_bytes(inner.bytes(), "big")
+        self.auth_key = pow(g_b, int.from_bytes(self.a, "big"), DH_PRIME).to_bytes(256, "big")
+
+        constructor, number = (0x46dc1fb9, 2) if retry else (0x3bcbf734, 1)
+        new_nonce_hash = tgcrypto.new_nonce_hash(self.new_nonce, self.auth_key, number)
+        return plain(struct.pack("<I", constructor) + self.nonce + self.server_nonce + new_nonce_hash)
+
+
+class TestAuthKeyHandshake(unittest.TestCase):
+    @classmethod
+    def setUpClass(cls):
+        cls.fingerprint = tgcrypto.add_public_key(TEST_KEY_PEM)
+
+    def test_permanent_key(self):
+        server = Server(self.fingerprint)
+        handshake = tgcrypto.AuthKeyHandshake(2)
+
+        packet = handshake.feed(server.res_pq(handshake.start()))
+        packet = handshake.feed(server.server_dh_params(packet, 0xa9f55f95))
+        self.assertIsNone(handshake.auth_key)
+        self.assertIsNone(handshake.feed(server.dh_gen(packet, retry=False)))
+
+        self.assertTrue(handshake.done)
+        self.assertEqual(handshake.auth_key, server.auth_key)
+        self.assertEqual(handshake.auth_key_id, hashlib.sha1(server.auth_key).digest()[-8:])
+        self.assertEqual(handshake.server_salt, tgcrypto.server_salt(server.new_nonce, server.server_nonce))
+        self.assertIsNone(handshake.expires_at)
+
+    def test_temporary_key_with_retry(self):
+        server = Server(self.fingerprint)
+        handshake = tgcrypto.AuthKeyHandshake(2, expires_in=86400)
+
+        packet = handshake.feed(server.res_pq(handshake.start()))
+        packet = handshake.feed(server.server_dh_params(packet, 0x56fddf88))
+        packet = handshake.feed(server.dh_gen(packet, retry=True))
+        first_auth_key = server.auth_key
+        self.assertFalse(handshake.done)
+
+        self.assertIsNone(handshake.feed(server.dh_gen(packet, retry=False)))
+        self.assertNotEqual(handshake.auth_key, first_auth_key)
+        self.assertEqual(handshake.auth_key, server.auth_key)
+        # expires_at follows the server clock, which the test server pins to 1700000000
+        self.assertAlmostEqual(handshake.expires_at, 1700000000 + 86400, delta=5)
+
+    def test_errors(self):
+        server = Server(self.fingerprint)
+        handshake = tgcrypto.AuthKeyHandshake(2)
+
+        with self.assertRaisesRegex(ValueError, "not waiting"):
+            handshake.feed(plain(bytes(4)))
+
+        handshake.start()
+        with self.assertRaisesRegex(ValueError, "nonce mismatch"):
+            handshake.feed(server.res_pq(handshake.start()[:-16] + bytes(16)))
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -111,5 +111,38 @@ class TestIGE256Random(unittest.TestCase):
         )
 
 
+class TestIgeCipher(unittest.TestCase):
+    def test_matches_functions(self):
+        key, iv = os.urandom(32), os.urandom(32)
+        data = os.urandom(64)
+        cipher = tgcrypto.IgeCipher(key)
+
+        self.assertEqual(cipher.encrypt(data, iv), tgcrypto.ige256_encrypt(data, key, iv))
+        self.assertEqual(cipher.decrypt(data, iv), tgcrypto.ige256_decrypt(data, key, iv))
+
+    def test_chunked(self):
+        key, iv = os.urandom(32), os.urandom(32)
+        data = os.urandom(160)
+        cipher = tgcrypto.IgeCipher(key)
+
+        encryptor = cipher.encryptor(iv)
+        encrypted = encryptor.update(data[:48]) + encryptor.update(data[48:])
+        self.assertEqual(encrypted, cipher.encrypt(data, iv))
+
+        decryptor = cipher.decryptor(iv)
+        decrypted = decryptor.update(encrypted[:16])
+        decrypted += tgcrypto.ige256_decrypt(encrypted[16:], key, decryptor.iv)
+        self.assertEqual(decrypted, data)
+
+    def test_invalid_key_size(self):
+        with self.assertRaisesRegex(ValueError, r"Key must be 32 bytes"):
+            tgcrypto.IgeCipher(os.urandom(31))
+
+    def test_invalid_chunk_size(self):
+        encryptor = tgcrypto.IgeCipher(os.urandom(32)).encryptor(os.urandom(32))
+        with self.assertRaisesRegex(ValueError, r"Data length must be a multiple of 16"):
+            encryptor.update(os.urandom(15))
+
+
 if __name__ == "__main__":
     unittest.main()
@@ -3,14 +3,43 @@
 
 #[cfg(test)]
 mod tests {
+    use hmac::{Hmac, Mac};
+    use num_bigint::BigUint;
+    use sha2::Sha256;
+    use tgcrypto::transport::{Codec, Frame, Transport};
+    use tgcrypto::{aes, dh, factorization, fake_tls, mtproto, obfuscated, rsa, sha, Error};
+
+    fn test_auth_key() -> Vec<u8> {
+        (0..256).map(|i| (i * 7 + 3) as u8).collect()
+    }
+
+    /// Held by every test that adds or removes keys, the RSA registry is global and tests run in parallel
+    static RSA_REGISTRY: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+    fn lock_rsa_registry() -> std::sync::MutexGuard<'static, ()> {
+        RSA_REGISTRY.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Fingerprints of the production keys registered by default, which no test removes
+    const BUILTIN_FINGERPRINTS: [i64; 9] = [
+        -4344800451088585951,
+        847625836280919973,
+        1562291298945373506,
+        -5859577972006586033,
+        6491968696586960280,
+        -7395192255793472640,
+        2685959930972952888,
+        -3997872768018684475,
+        -4960899639492471258,
+    ];
+
     #[test]
     fn test_sha1() {
         // SHA1 test vector
         let input = b"hello world";
-        let expected = b"\x2f\x7f\xcc\x09\x29\x5a\x88\x61\x9a\x12\x2a\xad\x6a\x91\x51\xfd\x6f\x6c\x8e\x99";
-        
-        // This would be tested via Python bindings
-        let _ = (input, expected);
+        let expected = b"\x2a\xae\x6c\x35\xc9\x4f\xcf\xb4\x15\xdb\xe9\x5f\x40\x8b\x9c\xe9\x1e\xe8\x46\xed";
+
+        assert_eq!(&sha::sha1(input), expected);
     }
 
     #[test]
@@ -18,8 +47,8 @@ mod tests {
         // SHA256 test vector
         let input = b"hello world";
         let expected = b"\xb9\x4d\x27\xb9\x93\x4d\x3e\x08\xa5\x2e\x52\xd7\xda\x7d\xab\xfa\xc4\x84\xef\xe3\x7a\x53\x80\xee\x90\x88\xf7\xac\xe2\xef\xcd\xe9";
-        
-        let _ = (input, expected);
+
+        assert_eq!(&sha::sha256(input), expected);
     }
 
     #[test]
@@ -28,8 +57,50 @@ mod tests {
         let key = [0u8; 32];
         let iv = [0u8; 32];
         let plaintext = b"Hello, World!123"; // 16 bytes
-        
-        let _ = (key, iv, plaintext);
+
+        let mut data = plaintext.to_vec();
+        aes::ige256_encrypt(&mut data, &key, &iv).unwrap();
+        assert_ne!(&data[..], plaintext);
+        aes::ige256_decrypt(&mut data, &key, &iv).unwrap();
+        assert_eq!(&data[..], plaintext);
+    }
+
+    #[test]
+    fn test_aes_ige_invalid_input() {
+        let mut data = [0u8; 15];
+        assert_eq!(
+            aes::ige256_encrypt(&mut data, &[0u8; 32], &[0u8; 32]),
+            Err(Error::InvalidDataLength)
+        );
+        assert_eq!(
+            aes::ige256_encrypt(&mut data, &[0u8; 31], &[0u8; 32]),
+            Err(Error::InvalidKeyLength { expected: 32, actual: 31 })
+        );
+    }
+
+    #[test]
+    fn test_aes_ige_cipher_chunked() {
+        // Chunked processing must match a single call under the same key schedule
+        let cipher = aes::Ige256::new(&[7u8; 32]).unwrap();
+        let iv = [9u8; 32];
+        let plaintext: Vec<u8> = (0..96u8).collect();
+
+        let mut whole = plaintext.clone();
+        cipher.encrypt(&mut whole, &iv).unwrap();
+
+        let mut stream = cipher.encryptor(&iv).unwrap();
+        let mut chunked = plaintext.clone();
+        let (head, tail) = chunked.split_at_mut(32);
+        stream.update(head).unwrap();
+        stream.update(tail).unwrap();
+        assert_eq!(whole, chunked);
+
+        let mut stream = cipher.decryptor(&iv).unwrap();
+        let (head, tail) = chunked.split_at_mut(48);
+        stream.update(head).unwrap();
+        // The carried IV continues with the stateless function
+        aes::ige256_decrypt(tail, &[7u8; 32], &stream.iv()).unwrap();
+        assert_eq!(chunked, plaintext);
     }
 
     #[test]
@@ -38,8 +109,53 @@ mod tests {
         let key = [0u8; 32];
         let iv = [0u8; 16];
         let plaintext = b"Hello, World!";
-        
-        let _ = (key, iv, plaintext);
+
+        let mut data = plaintext.to_vec();
+        let (mut enc_iv, mut enc_state) = (iv, 0u8);
+        aes::ctr256_encrypt(&mut data, &key, &mut enc_iv, &mut enc_state).unwrap();
+        assert_eq!(enc_state, 13);
+
+        let (mut dec_iv, mut dec_state) = (iv, 0u8);
+        aes::ctr256_decrypt(&mut data, &key, &mut dec_iv, &mut dec_state).unwrap();
+        assert_eq!(&data[..], plaintext);
+        assert_eq!((enc_iv, enc_state), (dec_iv, dec_state));
+    }
+
+    #[test]
+    fn test_aes_ctr_invalid_state() {
+        let mut state = 16u8;
+        assert_eq!(
+            aes::ctr256_encrypt(&mut [0u8; 4], &[0u8; 32], &mut [0u8; 16], &mut state),
+            Err(Error::InvalidState(16))
+        );
+    }
+
+    #[test]
+    fn test_aes_ctr_stream_seek() {
+        let key = [3u8; 32];
+        let iv = [0xffu8; 16]; // Counter wraps around after the first block
+        let plaintext: Vec<u8> = (0..100u8).collect();
+
+        let mut whole = plaintext.clone();
+        let mut stream = aes::CtrStream::new(&key, &iv, 0).unwrap();
+        stream.process(&mut whole);
+        assert_eq!(stream.tell(), 100);
+
+        // Matches the legacy iv/state convention
+        let mut legacy = plaintext.clone();
+        aes::ctr256_encrypt(&mut legacy, &key, &mut iv.clone(), &mut 0).unwrap();
+        assert_eq!(whole, legacy);
+
+        let mut tail = plaintext[37..].to_vec();
+        let mut stream = aes::CtrStream::new(&key, &iv, 37).unwrap();
+        stream.process(&mut tail);
+        assert_eq!(tail, whole[37..]);
+
+        stream.seek(5);
+        let mut part = plaintext[5..20].to_vec();
+        stream.process(&mut part);
+        assert_eq!(part, whole[5..20]);
+        assert_eq!(stream.tell(), 20);
     }
 
     #[test]
@@ -48,25 +164,1192 @@ mod tests {
         let key = [0u8; 32];
         let iv = [0u8; 16];
         let plaintext = b"Hello, World!123"; // 16 bytes
-        
-        let _ = (key, iv, plaintext);
+
+        let mut data = plaintext.to_vec();
+        let mut enc_iv = iv;
+        aes::cbc256_encrypt(&mut data, &key, &mut enc_iv).unwrap();
+        assert_eq!(enc_iv[..], data[..]);
+
+        let mut dec_iv = iv;
+        aes::cbc256_decrypt(&mut data, &key, &mut dec_iv).unwrap();
+        assert_eq!(&data[..], plaintext);
+        assert_eq!(enc_iv, dec_iv);
     }
 
     #[test]
     fn test_factorize() {
         // Test factorization with a known semiprime
         // 15 = 3 * 5
-        let pq: i128 = 15;
-        let factor = pq; // Would call factorize(pq)
-        
-        let _ = factor;
+        let pq = BigUint::from(15u32);
+        assert_eq!(factorization::factorize(&pq).unwrap(), (3u32.into(), 5u32.into()));
+
+        // pq from the MTProto documentation example
+        let pq = BigUint::from(0x17ED48941A08F981u64);
+        assert_eq!(factorization::factorize(&pq).unwrap(), (0x494C553Bu32.into(), 0x53911073u32.into()));
+
+        // Larger than 128 bits
+        let large_prime = (BigUint::from(1u32) << 127) - 1u32;
+        let pq = &large_prime * 1000003u32;
+        assert_eq!(factorization::factorize(&pq).unwrap(), (1000003u32.into(), large_prime));
+
+        // No silent fallback to pq itself
+        for pq in [0u32, 1, 3, 1000003] {
+            assert_eq!(factorization::factorize(&pq.into()), Err(Error::FactorizationFailed));
+        }
+    }
+
+    #[test]
+    fn test_factorize_word_sizes() {
+        // Largest 32-bit primes, pq just below 2^64
+        let (p, q) = (4294967279u64, 4294967291u64);
+        assert_eq!(factorization::factorize(&BigUint::from(p * q)).unwrap(), (p.into(), q.into()));
+
+        // 81 bits
+        let (p, q) = (1099511627689u128, 2199023255531u128);
+        assert_eq!(factorization::factorize(&BigUint::from(p * q)).unwrap(), (p.into(), q.into()));
+
+        // 128 bits, a 39-bit prime times 2^89 - 1
+        let (p, q) = (549755813881u128, (1u128 << 89) - 1);
+        assert_eq!((p * q).leading_zeros(), 0);
+        assert_eq!(factorization::factorize(&BigUint::from(p * q)).unwrap(), (p.into(), q.into()));
+
+        // Factors of 2 never reach the Montgomery path, which needs an odd modulus
+        assert_eq!(factorization::factorize(&BigUint::from(2 * q)).unwrap(), (2u32.into(), q.into()));
+    }
+
+    #[test]
+    fn test_factorize_seed_and_limit() {
+        let pq = BigUint::from(0x17ED48941A08F981u64);
+
+        // A seed reproduces the same run
+        let first = factorization::factorize_with(&pq, Some(42), None).unwrap();
+        assert_eq!(first, factorization::factorize_with(&pq, Some(42), None).unwrap());
+        assert_eq!((first.p, first.q, first.seed), (0x494C553Bu32.into(), 0x53911073u32.into(), 42));
+        assert!(first.iterations > 0);
+
+        let random = factorization::factorize_with(&pq, None, None).unwrap();
+        assert_eq!(random, factorization::factorize_with(&pq, Some(random.seed), None).unwrap());
+
+        // The limit is exact: the same run succeeds with just enough iterations
+        assert_eq!(
+            factorization::factorize_with(&pq, Some(42), Some(first.iterations)).unwrap().iterations,
+            first.iterations
+        );
+        assert_eq!(
+            factorization::factorize_with(&pq, Some(42), Some(first.iterations - 1)),
+            Err(Error::IterationLimit { iterations: first.iterations - 1, seed: 42 })
+        );
+
+        // Large factors: the limit is the only way out in reasonable time, and the error carries the drawn seed
+        let pq = ((BigUint::from(1u32) << 61) - 1u32) * ((BigUint::from(1u32) << 89) - 1u32);
+        let Err(Error::IterationLimit { iterations: 10000, seed }) = factorization::factorize_with(&pq, None, Some(10000))
+        else {
+            panic!("expected IterationLimit");
+        };
+        assert_eq!(
+            factorization::factorize_with(&pq, Some(seed), Some(10000)),
+            Err(Error::IterationLimit { iterations: 10000, seed })
+        );
+        assert!(Error::IterationLimit { iterations: 10000, seed }.to_string().contains(&format!("(seed {seed})")));
+
+        // Primes are rejected before iterating
+        let prime = (BigUint::from(1u32) << 127) - 1u32;
+        assert_eq!(factorization::factorize_with(&prime, None, Some(0)), Err(Error::FactorizationFailed));
+
+        // Even numbers and small values never iterate
+        assert_eq!(factorization::factorize_with(&BigUint::from(10u32), None, Some(0)).unwrap().iterations, 0);
+        assert_eq!(factorization::factorize_with(&BigUint::from(3u32), None, Some(0)), Err(Error::FactorizationFailed));
+    }
+
+    #[test]
+    fn test_is_prime() {
+        let sieve: Vec<bool> = (0..10000u32).map(|n| n >= 2 && (2..n).take_while(|d| d * d <= n).all(|d| n % d != 0)).collect();
+        for (n, &prime) in sieve.iter().enumerate() {
+            assert_eq!(factorization::is_prime(&BigUint::from(n)), prime, "{n}");
+        }
+
+        let mersenne = |k: u32| (BigUint::from(1u32) << k) - 1u32;
+        for k in [61, 89, 127, 521] {
+            assert!(factorization::is_prime(&mersenne(k)));
+        }
+        assert!(factorization::is_prime(&BigUint::from(u64::MAX - 58)));
+        assert!(factorization::is_prime(&BigUint::parse_bytes(TELEGRAM_DH_PRIME.as_bytes(), 16).unwrap()));
+
+        // Strong pseudoprimes to base 2, the last two to every base up to 31 and 37
+        for n in ["2047", "3215031751", "3825123056546413051", "318665857834031151167461", "3317044064679887385961981"] {
+            assert!(!factorization::is_prime(&n.parse().unwrap()), "{n}");
+        }
+        // Strong Lucas pseudoprimes, Carmichael numbers, composite Mersenne numbers and squares
+        for n in [5459u64, 5777, 10877, 561, 41041, 825265, u64::MAX] {
+            assert!(!factorization::is_prime(&BigUint::from(n)), "{n}");
+        }
+        assert!(!factorization::is_prime(&mersenne(67)));
+        assert!(!factorization::is_prime(&mersenne(127).pow(2)));
+        assert!(!factorization::is_prime(&(mersenne(89) * mersenne(107))));
+    }
+
+    #[test]
+    fn test_factor_all() {
+        let factors = |n: &BigUint| factorization::factor_all(n).unwrap();
+        let big = |n: u64| BigUint::from(n);
+        let mersenne = |k: u32| (BigUint::from(1u32) << k) - 1u32;
+
+        assert_eq!(factors(&big(1)), vec![]);
+        assert_eq!(factors(&big(2)), vec![(big(2), 1)]);
+        assert_eq!(factors(&big(360)), vec![(big(2), 3), (big(3), 2), (big(5), 1)]);
+        assert_eq!(factors(&big(0x17ED48941A08F981)), vec![(big(0x494C553B), 1), (big(0x53911073), 1)]);
+        assert_eq!(factors(&big(u64::MAX)), [3, 5, 17, 257, 641, 65537, 6700417].map(|p| (big(p), 1)));
+        assert_eq!(factors(&mersenne(127)), vec![(mersenne(127), 1)]);
+
+        // pq with a large prime cofactor
+        assert_eq!(
+            factors(&(big(0x17ED48941A08F981) * mersenne(89))),
+            vec![(big(0x494C553B), 1), (big(0x53911073), 1), (mersenne(89), 1)]
+        );
+
+        // Prime powers above the trial division bound
+        let n = big(1009).pow(5) * mersenne(61).pow(3) * big(1000003) * big(7).pow(4);
+        assert_eq!(factors(&n), vec![(big(7), 4), (big(1009), 5), (big(1000003), 1), (mersenne(61), 3)]);
+
+        assert_eq!(factorization::factor_all(&big(0)), Err(Error::FactorizationFailed));
+
+        // The limit covers every split together and a seed replays the run
+        let n = big(360) * mersenne(61) * mersenne(89);
+        assert_eq!(
+            factorization::factor_all_with(&n, Some(3), Some(1000)),
+            Err(Error::IterationLimit { iterations: 1000, seed: 3 })
+        );
+        let n = big(1009).pow(2) * big(1000003) * big(0x17ED48941A08F981);
+        let expected = vec![(big(1009), 2), (big(1000003), 1), (big(0x494C553B), 1), (big(0x53911073), 1)];
+        assert_eq!(factorization::factor_all_with(&n, Some(3), None).unwrap(), expected);
+        // Prime cofactors never iterate
+        assert_eq!(factorization::factor_all_with(&(big(360) * mersenne(127)), None, Some(0)).unwrap().len(), 4);
     }
 
     #[test]
     fn test_get_session_id() {
         // Test session ID generation
         let auth_key = [0u8; 256];
-        
-        let _ = auth_key;
+
+        let mut expected = [0u8; 8];
+        expected.copy_from_slice(&sha::sha1(&auth_key)[..8]);
+        expected.reverse();
+        assert_eq!(mtproto::get_session_id(&auth_key), expected);
+    }
+
+    #[test]
+    fn test_mtproto_message_roundtrip() {
+        let auth_key = test_auth_key();
+        let plaintext: Vec<u8> = (0..52u8).collect();
+
+        let packet = mtproto::encrypt_message(&auth_key, &plaintext, 0).unwrap();
+        assert_eq!(packet[..8], mtproto::auth_key_id(&auth_key));
+        assert_eq!((packet.len() - 24) % 16, 0);
+
+        let data = mtproto::decrypt_message(&auth_key, &packet, 0).unwrap();
+        assert_eq!(data[..52], plaintext[..]);
+        assert!((12..=27).contains(&(data.len() - 52)));
+
+        // msg_key = SHA256(auth_key[88:120] + plaintext + padding)[8:24]
+        let msg_key = sha::sha256(&[&auth_key[88..120], &data[..]].concat());
+        assert_eq!(packet[8..24], msg_key[8..24]);
+
+        // Decrypting with the other direction fails authentication
+        assert_eq!(mtproto::decrypt_message(&auth_key, &packet, 8), Err(Error::MsgKeyMismatch));
+    }
+
+    #[test]
+    fn test_mtproto_message_tampered() {
+        let auth_key = test_auth_key();
+        let mut packet = mtproto::encrypt_message(&auth_key, &[1u8; 40], 8).unwrap();
+
+        let last = packet.len() - 1;
+        packet[last] ^= 1;
+        assert_eq!(mtproto::decrypt_message(&auth_key, &packet, 8), Err(Error::MsgKeyMismatch));
+
+        packet[0] ^= 1;
+        assert_eq!(mtproto::decrypt_message(&auth_key, &packet, 8), Err(Error::AuthKeyIdMismatch));
+
+        assert_eq!(
+            mtproto::decrypt_message(&auth_key, &packet[..30], 8),
+            Err(Error::InvalidPacketLength(30))
+        );
+    }
+
+    fn server_plaintext(session_id: &[u8], msg_id: i64, length: u32, body: &[u8]) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.extend_from_slice(&42i64.to_le_bytes());
+        data.extend_from_slice(session_id);
+        data.extend_from_slice(&msg_id.to_le_bytes());
+        data.extend_from_slice(&3i32.to_le_bytes());
+        data.extend_from_slice(&length.to_le_bytes());
+        data.extend_from_slice(body);
+        data
+    }
+
+    #[test]
+    fn test_mtproto_server_message_validation() {
+        let auth_key = test_auth_key();
+        let session_id = [5u8; 8];
+        let now = 1_700_000_000i64;
+        let msg_id = (now << 32) | 1;
+        let body = [9u8; 20];
+
+        let packet = mtproto::encrypt_message(&auth_key, &server_plaintext(&session_id, msg_id, 20, &body), 8).unwrap();
+        let message = mtproto::decrypt_server_message(&auth_key, &packet, &session_id, now).unwrap();
+        assert_eq!(message.salt, 42);
+        assert_eq!(message.msg_id, msg_id);
+        assert_eq!(message.seq_no, 3);
+        assert_eq!(message.body, body);
+
+        assert_eq!(
+            mtproto::decrypt_server_message(&auth_key, &packet, &[6u8; 8], now),
+            Err(Error::SessionMismatch)
+        );
+        assert_eq!(
+            mtproto::decrypt_server_message(&auth_key, &packet, &session_id, now + 301),
+            Err(Error::BadMsgId(msg_id))
+        );
+        // The window saturates instead of overflowing at extreme times
+        for now in [i64::MIN, i64::MIN + 299, i64::MAX - 29, i64::MAX] {
+            assert_eq!(
+                mtproto::decrypt_server_message(&auth_key, &packet, &session_id, now),
+                Err(Error::BadMsgId(msg_id))
+            );
+        }
+        let latest = i64::MAX;
+        let packet = mtproto::encrypt_message(&auth_key, &server_plaintext(&session_id, latest, 20, &body), 8).unwrap();
+        assert_eq!(mtproto::decrypt_server_message(&auth_key, &packet, &session_id, latest >> 32).unwrap().msg_id, latest);
+
+        let even = mtproto::encrypt_message(&auth_key, &server_plaintext(&session_id, msg_id + 1, 20, &body), 8).unwrap();
+        assert_eq!(
+            mtproto::decrypt_server_message(&auth_key, &even, &session_id, now),
+            Err(Error::BadMsgId(msg_id + 1))
+        );
+
+        let too_long = mtproto::encrypt_message(&auth_key, &server_plaintext(&session_id, msg_id, 64, &body), 8).unwrap();
+        assert_eq!(
+            mtproto::decrypt_server_message(&auth_key, &too_long, &session_id, now),
+            Err(Error::BadMessageLength(64))
+        );
+
+        // Declaring a shorter body leaves more than 1024 bytes of padding
+        let long_body = [0u8; 1100];
+        let padded = mtproto::encrypt_message(&auth_key, &server_plaintext(&session_id, msg_id, 4, &long_body), 8).unwrap();
+        assert!(matches!(
+            mtproto::decrypt_server_message(&auth_key, &padded, &session_id, now),
+            Err(Error::BadPadding(_))
+        ));
+    }
+
+    #[test]
+    fn test_mtproto_v1_message_roundtrip() {
+        let auth_key = test_auth_key();
+        let plaintext = server_plaintext(&[1u8; 8], 12345, 12, &[7u8; 12]);
+
+        let packet = mtproto::encrypt_message_v1(&auth_key, &plaintext, 0).unwrap();
+        assert_eq!(packet[8..24], sha::sha1(&plaintext)[4..20]);
+
+        let msg_key: [u8; 16] = packet[8..24].try_into().unwrap();
+        let (aes_key, aes_iv) = mtproto::kdf_v1(&auth_key, &msg_key, 0).unwrap();
+        let mut data = packet[24..].to_vec();
+        aes::ige256_decrypt(&mut data, &aes_key, &aes_iv).unwrap();
+        assert_eq!(data[..plaintext.len()], plaintext[..]);
+
+        assert_eq!(mtproto::decrypt_message_v1(&auth_key, &packet, 0).unwrap(), plaintext);
+        // A wrong direction yields garbage that is rejected by either check
+        assert!(matches!(
+            mtproto::decrypt_message_v1(&auth_key, &packet, 8),
+            Err(Error::BadMessageLength(_) | Error::MsgKeyMismatch)
+        ));
+    }
+
+    #[test]
+    fn test_mtproto_v1_secret_message_roundtrip() {
+        let auth_key = test_auth_key();
+        let payload = b"decryptedMessageLayer".repeat(3);
+
+        let packet = mtproto::encrypt_secret_message_v1(&auth_key, &payload, 0).unwrap();
+        let plaintext = [&(payload.len() as u32).to_le_bytes()[..], &payload].concat();
+        assert_eq!(packet[8..24], sha::sha1(&plaintext)[4..20]);
+
+        // Decrypted by hand: int32 length, payload, then at most 15 bytes of padding
+        let msg_key: [u8; 16] = packet[8..24].try_into().unwrap();
+        let (aes_key, aes_iv) = mtproto::kdf_v1(&auth_key, &msg_key, 0).unwrap();
+        let mut data = packet[24..].to_vec();
+        aes::ige256_decrypt(&mut data, &aes_key, &aes_iv).unwrap();
+        assert_eq!(data[..plaintext.len()], plaintext[..]);
+        assert!(data.len() - plaintext.len() < 16);
+
+        assert_eq!(mtproto::decrypt_secret_message_v1(&auth_key, &packet, 0).unwrap(), payload);
+        // The MTProto layout reads a length at offset 28 that isn't there
+        assert!(mtproto::decrypt_message_v1(&auth_key, &packet, 0).is_err());
+
+        // A 1-byte payload fits in a single block
+        let packet = mtproto::encrypt_secret_message_v1(&auth_key, &[7], 0).unwrap();
+        assert_eq!(packet.len(), 24 + 16);
+        assert_eq!(mtproto::decrypt_secret_message_v1(&auth_key, &packet, 0).unwrap(), [7]);
+        assert_eq!(mtproto::decrypt_secret_message_v1(&auth_key, &packet[..24], 0), Err(Error::InvalidPacketLength(24)));
+    }
+
+    #[test]
+    fn test_obfuscated2_init_packet() {
+        let random: [u8; 64] = std::array::from_fn(|i| (i as u8).wrapping_mul(31).wrapping_add(1));
+        let secret = [0x42u8; 16];
+        let mut client =
+            obfuscated::Obfuscated2::with_random(random, &obfuscated::INTERMEDIATE, -2, Some(&secret)).unwrap();
+        let init = *client.init_packet();
+        assert_eq!(init[..56], random[..56]);
+
+        // What the other side derives from the header it receives
+        let key_iv = &init[8..56];
+        let key = sha::sha256(&[&key_iv[..32], &secret[..]].concat());
+        let mut server_decryptor = aes::CtrStream::new(&key, &key_iv[32..], 0).unwrap();
+        let mut header = init;
+        server_decryptor.process(&mut header);
+        assert_eq!(header[56..60], obfuscated::INTERMEDIATE);
+        assert_eq!(header[60..62], (-2i16).to_le_bytes());
+
+        let mut data = *b"hello";
+        client.encryptor().process(&mut data);
+        server_decryptor.process(&mut data);
+        assert_eq!(&data, b"hello");
+
+        let reversed: Vec<u8> = key_iv.iter().rev().copied().collect();
+        let key = sha::sha256(&[&reversed[..32], &secret[..]].concat());
+        let mut server_encryptor = aes::CtrStream::new(&key, &reversed[32..], 0).unwrap();
+        let mut data = *b"world";
+        server_encryptor.process(&mut data);
+        client.decryptor().process(&mut data);
+        assert_eq!(&data, b"world");
+    }
+
+    #[test]
+    fn test_obfuscated2_invalid_input() {
+        let mut random = [1u8; 64];
+        random[..4].copy_from_slice(b"POST");
+        assert!(matches!(
+            obfuscated::Obfuscated2::with_random(random, &obfuscated::ABRIDGED, 2, None),
+            Err(Error::InvalidInitPacket)
+        ));
+        assert!(matches!(
+            obfuscated::Obfuscated2::new(b"abcd", 2, None),
+            Err(Error::InvalidProtocolTag)
+        ));
+        assert!(matches!(
+            obfuscated::Obfuscated2::new(&obfuscated::ABRIDGED, 2, Some(&[0u8; 15])),
+            Err(Error::InvalidSecretLength(15))
+        ));
+
+        let client = obfuscated::Obfuscated2::new(&obfuscated::ABRIDGED, 2, None).unwrap();
+        assert_ne!(client.init_packet()[0], 0xef);
+    }
+
+    fn hmac_sha256(key: &[u8], data: &[u8]) -> [u8; 32] {
+        let mut mac = Hmac::<Sha256>::new_from_slice(key).unwrap();
+        mac.update(data);
+        mac.finalize().into_bytes().into()
+    }
+
+    #[test]
+    fn test_fake_tls_client_hello() {
+        let mut secret = vec![0xee];
+        secret.extend_from_slice(&[0x11u8; 16]);
+        secret.extend_from_slice(b"example.com");
+
+        let client = fake_tls::FakeTlsClient::with_timestamp(&secret, &obfuscated::PADDED_INTERMEDIATE, 2, 0x12345678).unwrap();
+        let hello = client.client_hello();
+        assert_eq!(hello.len(), 517);
+        assert_eq!(hello[..3], [0x16, 0x03, 0x01]);
+        assert!(hello.windows(11).any(|w| w == b"example.com"));
+
+        // random = HMAC(key, hello with zeroed random), last 4 bytes XOR'ed with the timestamp
+        let mut zeroed = hello.to_vec();
+        zeroed[11..43].fill(0);
+        let mut expected = hmac_sha256(&[0x11u8; 16], &zeroed);
+        for (byte, t) in expected[28..].iter_mut().zip(0x12345678u32.to_le_bytes()) {
+            *byte ^= t;
+        }
+        assert_eq!(hello[11..43], expected);
+    }
+
+    #[test]
+    fn test_fake_tls_server_hello_fixture() {
+        let mut secret = vec![0xee];
+        secret.extend_from_slice(&[0x22u8; 16]);
+        secret.extend_from_slice(b"example.com");
+        let mut client = fake_tls::FakeTlsClient::new(&secret, &obfuscated::PADDED_INTERMEDIATE, 2).unwrap();
+
+        // The fixture has a zeroed random, filled with the HMAC a real server would send
+        let fixture = include_bytes!("fixtures/fake_tls_server_hello.bin");
+        let mut response = fixture.to_vec();
+        let digest = hmac_sha256(&[0x22u8; 16], &[&client.client_hello()[11..43], &fixture[..]].concat());
+        response[11..43].copy_from_slice(&digest);
+
+        assert_eq!(client.verify_server_hello(&response[..100]), Ok(None));
+        assert_eq!(client.verify_server_hello(&response), Ok(Some(fixture.len())));
+
+        response[50] ^= 1;
+        assert_eq!(client.verify_server_hello(&response), Err(Error::ServerHelloMismatch));
+
+        // Traffic after the handshake: ChangeCipherSpec + application data with the init header
+        let packet = client.encrypt(b"ping");
+        assert_eq!(packet[..6], [0x14, 0x03, 0x03, 0x00, 0x01, 0x01]);
+        assert_eq!(packet[6..11], [0x17, 0x03, 0x03, 0x00, 68]);
+        let packet = client.encrypt(b"pong");
+        assert_eq!(packet[..5], [0x17, 0x03, 0x03, 0x00, 4]);
+
+        let mut records = fake_tls::RecordLayer::default();
+        let wrapped = records.wrap(&[1u8; 20000]);
+        // Only complete records are returned
+        assert_eq!(client.decrypt(&wrapped[..100]).unwrap().len(), 0);
+        assert_eq!(client.decrypt(&wrapped[100..]).unwrap().len(), 20000);
+        assert_eq!(client.decrypt(&[0x15, 0x03, 0x03, 0x00, 0x00]), Err(Error::BadTlsRecord));
+    }
+
+    #[test]
+    fn test_obfuscated2_server() {
+        let secrets: [&[u8]; 3] = [&[0x33u8; 16], b"", &[0x44u8; 16]];
+        for (index, secret) in [(0, Some(&[0x33u8; 16][..])), (1, None), (2, Some(&[0x44u8; 16][..]))] {
+            let mut client = obfuscated::Obfuscated2::new(&obfuscated::INTERMEDIATE, -4, secret).unwrap();
+            let mut server = obfuscated::Obfuscated2Server::accept(client.init_packet(), &secrets).unwrap();
+            assert_eq!(server.secret_index(), index);
+            assert_eq!(server.tag(), obfuscated::INTERMEDIATE);
+            assert_eq!(server.dc_id(), -4);
+
+            let mut data = *b"hello from the client";
+            client.encryptor().process(&mut data);
+            server.decryptor().process(&mut data);
+            assert_eq!(&data, b"hello from the client");
+
+            server.encryptor().process(&mut data);
+            client.decryptor().process(&mut data);
+            assert_eq!(&data, b"hello from the client");
+        }
+
+        let client = obfuscated::Obfuscated2::new(&obfuscated::ABRIDGED, 2, Some(&[0x55u8; 16])).unwrap();
+        assert!(matches!(
+            obfuscated::Obfuscated2Server::accept(client.init_packet(), &secrets),
+            Err(Error::UnknownSecret)
+        ));
+        assert!(matches!(
+            obfuscated::Obfuscated2Server::accept(&client.init_packet()[..63], &secrets),
+            Err(Error::InvalidInitPacket)
+        ));
+
+        // A malformed secret is skipped rather than failing every client
+        let with_bad: [&[u8]; 2] = [&[0x55u8; 5], &[0x55u8; 16]];
+        assert_eq!(obfuscated::Obfuscated2Server::accept(client.init_packet(), &with_bad).unwrap().secret_index(), 1);
+        assert!(matches!(
+            obfuscated::Obfuscated2Server::accept(client.init_packet(), &with_bad[..1]),
+            Err(Error::UnknownSecret)
+        ));
+    }
+
+    #[test]
+    fn test_fake_tls_server() {
+        let mut secret = vec![0xee];
+        secret.extend_from_slice(&[0x66u8; 16]);
+        secret.extend_from_slice(b"example.com");
+        let secrets: [&[u8]; 2] = [&[0x77u8; 16], &secret];
+
+        let mut client = fake_tls::FakeTlsClient::with_timestamp(&secret, &obfuscated::ABRIDGED, 3, 1_700_000_000).unwrap();
+        let hello = client.client_hello().to_vec();
+        let mut server = fake_tls::FakeTlsServer::accept_at(&hello, &secrets, 1_700_000_100, 120).unwrap();
+        assert_eq!(server.secret_index(), 1);
+        assert_eq!(server.timestamp(), 1_700_000_000);
+        assert_eq!(client.verify_server_hello(server.server_hello()), Ok(Some(server.server_hello().len())));
+
+        assert_eq!(server.tag(), None);
+        assert!(matches!(server.encrypt(b"early"), Err(Error::InitPacketPending)));
+
+        // The init header may be split across records
+        let packet = client.encrypt(b"ping");
+        assert_eq!(server.decrypt(&packet[..40]).unwrap(), b"");
+        assert_eq!(server.decrypt(&packet[40..]).unwrap(), b"ping");
+        assert_eq!(server.tag(), Some(obfuscated::ABRIDGED));
+        assert_eq!(server.dc_id(), Some(3));
+
+        let reply = server.encrypt(b"pong").unwrap();
+        assert_eq!(reply[..5], [0x17, 0x03, 0x03, 0x00, 4]);
+        assert_eq!(client.decrypt(&reply).unwrap(), b"pong");
+
+        assert!(matches!(
+            fake_tls::FakeTlsServer::accept_at(&hello, &secrets, 1_700_000_121, 120),
+            Err(Error::ClientHelloExpired(1_700_000_000))
+        ));
+        assert!(matches!(
+            fake_tls::FakeTlsServer::accept_at(&hello, &secrets[..1], 1_700_000_000, 120),
+            Err(Error::UnknownSecret)
+        ));
+        assert!(matches!(
+            fake_tls::FakeTlsServer::accept_at(&hello[..516], &secrets, 1_700_000_000, 120),
+            Err(Error::BadClientHello)
+        ));
+
+        // The first records may arrive in the same read as the ClientHello
+        let mut client = fake_tls::FakeTlsClient::with_timestamp(&secret, &obfuscated::INTERMEDIATE, -2, 1_700_000_000).unwrap();
+        let mut combined = client.client_hello().to_vec();
+        combined.extend_from_slice(&client.encrypt(b"early ping"));
+        let mut server = fake_tls::FakeTlsServer::accept_at(&combined, &secrets, 1_700_000_000, 120).unwrap();
+        assert_eq!(client.verify_server_hello(server.server_hello()), Ok(Some(server.server_hello().len())));
+        assert_eq!(server.decrypt(&[]).unwrap(), b"early ping");
+        assert_eq!(server.dc_id(), Some(-2));
+        assert_eq!(server.decrypt(&client.encrypt(b"next")).unwrap(), b"next");
+
+        // A partial trailing record waits for the rest
+        let mut client = fake_tls::FakeTlsClient::with_timestamp(&secret, &obfuscated::INTERMEDIATE, 2, 1_700_000_000).unwrap();
+        let mut combined = client.client_hello().to_vec();
+        let packet = client.encrypt(b"split");
+        combined.extend_from_slice(&packet[..20]);
+        let mut server = fake_tls::FakeTlsServer::accept_at(&combined, &secrets, 1_700_000_000, 120).unwrap();
+        assert_eq!(server.decrypt(&[]).unwrap(), b"");
+        assert_eq!(server.decrypt(&packet[20..]).unwrap(), b"split");
+
+        // An incomplete ClientHello is still rejected
+        assert!(matches!(
+            fake_tls::FakeTlsServer::accept_at(&hello[..hello.len() - 1], &secrets, 1_700_000_000, 120),
+            Err(Error::BadClientHello)
+        ));
+
+        // A malformed secret is skipped rather than failing every client
+        let with_bad: [&[u8]; 2] = [b"\xee", &secret];
+        assert_eq!(fake_tls::FakeTlsServer::accept_at(&hello, &with_bad, 1_700_000_000, 120).unwrap().secret_index(), 1);
+        assert!(matches!(
+            fake_tls::FakeTlsServer::accept_at(&hello, &with_bad[..1], 1_700_000_000, 120),
+            Err(Error::UnknownSecret)
+        ));
+    }
+
+    #[test]
+    fn test_transport_roundtrip() {
+        let small = vec![0x42u8; 40];
+        let large = vec![0x24u8; 4 * 0x100];
+        for transport in [Transport::Abridged, Transport::Intermediate, Transport::PaddedIntermediate, Transport::Full] {
+            let (mut sender, mut receiver) = (Codec::new(transport), Codec::new(transport));
+            let mut stream = Vec::new();
+            for packet in [&small, &large, &small] {
+                stream.extend_from_slice(&sender.encode(packet).unwrap());
+            }
+
+            // Byte-at-a-time feeding yields the same frames as a single call
+            let mut frames = Vec::new();
+            for byte in &stream {
+                frames.extend(receiver.feed(std::slice::from_ref(byte)).unwrap());
+            }
+            assert_eq!(frames.len(), 3);
+            for (frame, packet) in frames.iter().zip([&small, &large, &small]) {
+                let Frame::Packet(data) = frame else { panic!("unexpected {:?}", frame) };
+                assert_eq!(&data[..packet.len()], &packet[..]);
+                assert!(data.len() - packet.len() < 16);
+            }
+        }
+    }
+
+    #[test]
+    fn test_transport_framing() {
+        let mut codec = Codec::new(Transport::Abridged);
+        assert_eq!(codec.encode(&[1u8; 8]).unwrap(), [&[2u8][..], &[1u8; 8]].concat());
+        assert_eq!(codec.encode(&[1u8; 0x7f * 4]).unwrap()[..4], [0x7f, 0x7f, 0x00, 0x00]);
+        assert!(matches!(codec.encode(&[1u8; 6]), Err(Error::UnalignedPacket(6))));
+
+        let mut codec = Codec::new(Transport::Full);
+        let frame = codec.encode(b"abcd").unwrap();
+        assert_eq!(frame[..8], [16, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(frame[12..], crc32fast::hash(&frame[..12]).to_le_bytes());
+        assert_eq!(codec.encode(b"abcd").unwrap()[4..8], [1, 0, 0, 0]);
+
+        // Transport errors are a lone negative int
+        let mut codec = Codec::new(Transport::Intermediate);
+        let frames = codec.feed(&[4, 0, 0, 0, 0x6c, 0xfe, 0xff, 0xff]).unwrap();
+        assert_eq!(frames, [Frame::Error(-404)]);
+        let mut codec = Codec::new(Transport::Abridged);
+        assert_eq!(codec.feed(&[1, 0x6c, 0xfe, 0xff, 0xff]).unwrap(), [Frame::Error(-404)]);
+
+        let mut codec = Codec::new(Transport::Full);
+        let mut frame = Codec::new(Transport::Full).encode(b"abcd").unwrap();
+        frame[9] ^= 1;
+        assert!(matches!(codec.feed(&frame), Err(Error::BadCrc)));
+
+        let mut sender = Codec::new(Transport::Full);
+        sender.encode(b"abcd").unwrap();
+        let frame = sender.encode(b"abcd").unwrap();
+        assert!(matches!(Codec::new(Transport::Full).feed(&frame), Err(Error::BadSeqNo(1))));
+
+        assert!(matches!(
+            Codec::new(Transport::Intermediate).feed(&[0xff, 0xff, 0xff, 0x7f]),
+            Err(Error::PacketTooLarge(_))
+        ));
+    }
+
+    #[test]
+    fn test_quick_ack() {
+        let auth_key = test_auth_key();
+        let (packet, token) = mtproto::encrypt_message_quick_ack(&auth_key, &[7u8; 40], 0).unwrap();
+        let data = mtproto::decrypt_message(&auth_key, &packet, 0).unwrap();
+
+        let hash = sha::sha256(&[&auth_key[88..120], &data[..]].concat());
+        assert_eq!(token, u32::from_le_bytes(hash[..4].try_into().unwrap()) | 0x8000_0000);
+        assert_eq!(packet[8..24], hash[8..24]);
+
+        // Requests carry the flag in the length, replies are the bare token
+        let mut codec = Codec::new(Transport::Abridged);
+        assert_eq!(codec.encode_quick_ack(&[0u8; 8]).unwrap()[0], 0x82);
+        let mut reply = token.to_be_bytes().to_vec();
+        reply.extend_from_slice(&codec.encode(&[5u8; 8]).unwrap());
+        assert_eq!(codec.feed(&reply[..2]).unwrap(), []);
+        assert_eq!(codec.feed(&reply[2..]).unwrap(), [Frame::QuickAck(token), Frame::Packet(vec![5u8; 8])]);
+
+        for transport in [Transport::Intermediate, Transport::PaddedIntermediate, Transport::Full] {
+            let mut codec = Codec::new(transport);
+            assert_eq!(codec.encode_quick_ack(&[0u8; 8]).unwrap()[3] & 0x80, 0x80);
+            assert_eq!(codec.feed(&token.to_le_bytes()).unwrap(), [Frame::QuickAck(token)]);
+        }
+    }
+
+    #[test]
+    fn test_rsa_pad_encrypt() {
+        let fingerprint = -4344800451088585951;
+        let first = rsa::rsa_pad_encrypt(&[1u8; 144], fingerprint).unwrap();
+        let second = rsa::rsa_pad_encrypt(&[1u8; 144], fingerprint).unwrap();
+        assert_eq!(first.len(), 256);
+        // Fresh padding and temp_key on every call
+        assert_ne!(first, second);
+
+        assert_eq!(
+            rsa::rsa_pad_encrypt(&[1u8; 145], fingerprint),
+            Err(Error::DataTooLong { max: 144, actual: 145 })
+        );
+        assert_eq!(rsa::rsa_pad_encrypt(&[1u8; 16], 1), Err(Error::UnknownFingerprint(1)));
+    }
+
+    #[test]
+    fn test_rsa_encrypt_validation() {
+        let fingerprint = -4344800451088585951;
+        assert_eq!(rsa::rsa_encrypt(&[1u8; 255], fingerprint).unwrap().len(), 256);
+        // Raw RSA of 0 and 1 is the input itself
+        let mut one = vec![0u8; 256];
+        one[255] = 1;
+        assert_eq!(rsa::rsa_encrypt(&[1], fingerprint).unwrap(), one);
+
+        assert_eq!(rsa::rsa_encrypt(&[0xffu8; 256], fingerprint), Err(Error::DataExceedsModulus));
+        assert_eq!(
+            rsa::rsa_encrypt(&[1u8; 257], fingerprint),
+            Err(Error::DataTooLong { max: 256, actual: 257 })
+        );
+
+        let padded = rsa::rsa_encrypt_padded(&[1u8; 200], fingerprint, rsa::Padding::Sha1).unwrap();
+        assert_eq!(padded.len(), 256);
+        assert_ne!(padded, rsa::rsa_encrypt_padded(&[1u8; 200], fingerprint, rsa::Padding::Sha1).unwrap());
+        assert!(rsa::rsa_encrypt_padded(&[1u8; 235], fingerprint, rsa::Padding::Sha1).is_ok());
+        assert_eq!(
+            rsa::rsa_encrypt_padded(&[1u8; 236], fingerprint, rsa::Padding::Sha1),
+            Err(Error::DataTooLong { max: 235, actual: 236 })
+        );
+    }
+
+    /// Raw RSA decryption with the private key matching tests/fixtures/rsa_test_key.pem
+    fn rsa_test_key_decrypt(encrypted: &[u8]) -> Vec<u8> {
+        let mut lines = include_str!("fixtures/rsa_test_key.hex").lines();
+        let n = BigUint::parse_bytes(lines.next().unwrap().as_bytes(), 16).unwrap();
+        let d = BigUint::parse_bytes(lines.next().unwrap().as_bytes(), 16).unwrap();
+        let decrypted = BigUint::from_bytes_be(encrypted).modpow(&d, &n).to_bytes_be();
+        [vec![0u8; 256 - decrypted.len()], decrypted].concat()
+    }
+
+    #[test]
+    fn test_rsa_key_registry() {
+        let _registry = lock_rsa_registry();
+        let keys = rsa::list_public_keys();
+        assert!(keys.contains(&-4344800451088585951));
+        assert!(!keys.contains(&-5595554452916591101));
+
+        // Test DC keys are opt-in
+        assert_eq!(rsa::add_test_dc_keys(), [-5595554452916591101]);
+        assert!(rsa::list_public_keys().contains(&-5595554452916591101));
+        assert!(rsa::remove_public_key(-5595554452916591101));
+        assert!(!rsa::remove_public_key(-5595554452916591101));
+
+        let fingerprint = rsa::add_public_key(include_str!("fixtures/rsa_test_key.pem")).unwrap();
+        assert!(rsa::list_public_keys().contains(&fingerprint));
+
+        // RSA_PAD round trip: undo the temp_key mask, AES-IGE and the reversal
+        let data = [0x5au8; 100];
+        let decrypted = rsa_test_key_decrypt(&rsa::rsa_pad_encrypt(&data, fingerprint).unwrap());
+        let (temp_key_xor, aes_encrypted) = decrypted.split_at(32);
+        let hash = sha::sha256(aes_encrypted);
+        let temp_key: Vec<u8> = temp_key_xor.iter().zip(hash).map(|(a, b)| a ^ b).collect();
+        let mut data_with_hash = aes_encrypted.to_vec();
+        aes::ige256_decrypt(&mut data_with_hash, &temp_key, &[0u8; 32]).unwrap();
+        let mut data_with_padding = data_with_hash[..192].to_vec();
+        data_with_padding.reverse();
+        assert_eq!(data_with_padding[..100], data);
+        assert_eq!(data_with_hash[192..], sha::sha256(&[&temp_key[..], &data_with_padding].concat()));
+
+        // Legacy SHA-1 padding round trip
+        let encrypted = rsa::rsa_encrypt_padded(&data, fingerprint, rsa::Padding::Sha1).unwrap();
+        let decrypted = rsa_test_key_decrypt(&encrypted);
+        assert_eq!(decrypted[0], 0);
+        assert_eq!(decrypted[1..21], sha::sha1(&data));
+        assert_eq!(decrypted[21..121], data);
+
+        assert!(rsa::remove_public_key(fingerprint));
+        assert_eq!(rsa::rsa_encrypt(&data, fingerprint), Err(Error::UnknownFingerprint(fingerprint)));
+        assert_eq!(rsa::add_public_key("-----BEGIN RSA PUBLIC KEY-----\nAAAA\n-----END RSA PUBLIC KEY-----"), Err(Error::InvalidPublicKey));
+    }
+
+    #[test]
+    fn test_rsa_fingerprint() {
+        // Every built-in fingerprint matches its modulus and exponent
+        for fingerprint in BUILTIN_FINGERPRINTS {
+            assert_eq!(rsa::get_public_key(fingerprint).unwrap().fingerprint(), fingerprint);
+        }
+        assert_eq!(rsa::get_public_key(-4344800451088585951).unwrap().exponent(), &BigUint::from(65537u32));
+
+        let pkcs1 = include_str!("fixtures/rsa_test_key.pem");
+        let spki = include_str!("fixtures/rsa_test_key_spki.pem");
+        let key = rsa::PublicKey::from_pem(spki).unwrap();
+        assert_eq!(key, rsa::PublicKey::from_pem(pkcs1).unwrap());
+        assert_eq!(rsa::rsa_fingerprint(spki), rsa::rsa_fingerprint(pkcs1));
+        assert_eq!(key.modulus().bits(), 2048);
+
+        let n = BigUint::parse_bytes(include_str!("fixtures/rsa_test_key.hex").lines().next().unwrap().as_bytes(), 16);
+        assert_eq!(Some(key.modulus().clone()), n);
+
+        assert_eq!(rsa::rsa_fingerprint(&spki.replace("PUBLIC KEY", "CERTIFICATE")), Err(Error::InvalidPublicKey));
+    }
+
+    /// SHA1(data) + data + random padding to a multiple of 16, IGE-encrypted with the tmp key
+    fn encrypt_dh_answer(data: &[u8], new_nonce: &[u8; 32], server_nonce: &[u8; 16]) -> Vec<u8> {
+        let new_server = sha::sha1(&[&new_nonce[..], server_nonce].concat());
+        let server_new = sha::sha1(&[&server_nonce[..], new_nonce].concat());
+        let new_new = sha::sha1(&[&new_nonce[..], new_nonce].concat());
+        let key = [&new_server[..], &server_new[..12]].concat();
+        let iv = [&server_new[12..], &new_new[..], &new_nonce[..4]].concat();
+
+        let mut answer = [&sha::sha1(data)[..], data].concat();
+        answer.resize(answer.len().next_multiple_of(16), 0xaa);
+        aes::ige256_encrypt(&mut answer, &key, &iv).unwrap();
+        answer
+    }
+
+    #[test]
+    fn test_server_dh_inner_data() {
+        let new_nonce = [0x11u8; 32];
+        let server_nonce = [0x22u8; 16];
+
+        let (key, iv) = mtproto::tmp_aes_key_iv(&new_nonce, &server_nonce);
+        assert_eq!(key[..20], sha::sha1(&[&new_nonce[..], &server_nonce].concat()));
+        assert_eq!(iv[28..], new_nonce[..4]);
+
+        for len in [0, 12, 564, 580] {
+            let data: Vec<u8> = (0..len).map(|i| (i * 13) as u8).collect();
+            let answer = encrypt_dh_answer(&data, &new_nonce, &server_nonce);
+            assert_eq!(mtproto::decrypt_server_dh_inner_data(&answer, &new_nonce, &server_nonce).unwrap(), data);
+        }
+
+        let mut answer = encrypt_dh_answer(&[7u8; 564], &new_nonce, &server_nonce);
+        assert_eq!(
+            mtproto::decrypt_server_dh_inner_data(&answer, &[0x12u8; 32], &server_nonce),
+            Err(Error::DhAnswerHashMismatch)
+        );
+        answer[40] ^= 1;
+        assert_eq!(
+            mtproto::decrypt_server_dh_inner_data(&answer, &new_nonce, &server_nonce),
+            Err(Error::DhAnswerHashMismatch)
+        );
+        assert_eq!(
+            mtproto::decrypt_server_dh_inner_data(&answer[..590], &new_nonce, &server_nonce),
+            Err(Error::InvalidPacketLength(590))
+        );
+    }
+
+    #[test]
+    fn test_client_dh_inner_data() {
+        let new_nonce = [0x33u8; 32];
+        let server_nonce = [0x44u8; 16];
+        let data = [0x55u8; 376];
+
+        let encrypted = mtproto::encrypt_client_dh_inner_data(&data, &new_nonce, &server_nonce).unwrap();
+        assert_eq!(encrypted.len(), 400);
+
+        let (key, iv) = mtproto::tmp_aes_key_iv(&new_nonce, &server_nonce);
+        let mut decrypted = encrypted.clone();
+        aes::ige256_decrypt(&mut decrypted, &key, &iv).unwrap();
+        assert_eq!(decrypted[..20], sha::sha1(&data));
+        assert_eq!(decrypted[20..396], data);
+
+        // Same layout as the server's answer
+        assert_eq!(mtproto::decrypt_server_dh_inner_data(&encrypted, &new_nonce, &server_nonce).unwrap(), data);
+    }
+
+    #[test]
+    fn test_new_nonce_hash() {
+        use tgcrypto::mtproto::DhGenStatus;
+
+        let new_nonce = [0x33u8; 32];
+        let server_nonce = [0x44u8; 16];
+        let auth_key = [0x66u8; 256];
+
+        assert_eq!(mtproto::auth_key_aux_hash(&auth_key), 0xa5ff36b8df5d62c3u64.to_be_bytes());
+
+        let expected = [
+            (DhGenStatus::Ok, 0x23bb44e02b0fb4742d60b4ee47055047u128),
+            (DhGenStatus::Retry, 0x305060b8bde4489a9cd34dce0b609851u128),
+            (DhGenStatus::Fail, 0x553f3548dd06d63aedf1dd90a78cf131u128),
+        ];
+        for (status, hash) in expected {
+            let hash = hash.to_be_bytes();
+            assert_eq!(mtproto::new_nonce_hash(&new_nonce, &auth_key, status), hash);
+            mtproto::check_new_nonce_hash(&new_nonce, &auth_key, status, &hash).unwrap();
+        }
+
+        let hash = mtproto::new_nonce_hash(&new_nonce, &auth_key, DhGenStatus::Ok);
+        assert_eq!(
+            mtproto::check_new_nonce_hash(&new_nonce, &auth_key, DhGenStatus::Retry, &hash),
+            Err(Error::NewNonceHashMismatch)
+        );
+        assert_eq!(
+            mtproto::check_new_nonce_hash(&new_nonce, &auth_key, DhGenStatus::Ok, &hash[..15]),
+            Err(Error::NewNonceHashMismatch)
+        );
+
+        assert_eq!(mtproto::server_salt(&new_nonce, &server_nonce), 8608480567731124087);
+    }
+
+    #[test]
+    fn test_bind_temp_auth_key() {
+        let perm_key = test_auth_key();
+        let random: [u8; 24] = std::array::from_fn(|i| i as u8 + 1);
+        let (nonce, temp_key_id, temp_session_id) = (0x0123456789abcdefi64, -0x1122334455667788i64, 0x0a0b0c0d0e0f1011i64);
+        let (msg_id, expires_at) = (0x6553f10000000004i64, 1700086400i32);
+        let perm_key_id = &sha::sha1(&perm_key)[12..];
+
+        // bind_auth_key_inner#75a3f765 nonce:long temp_auth_key_id:long perm_auth_key_id:long
+        //     temp_session_id:long expires_at:int
+        let mut inner = 0x75a3f765u32.to_le_bytes().to_vec();
+        inner.extend_from_slice(&nonce.to_le_bytes());
+        inner.extend_from_slice(&temp_key_id.to_le_bytes());
+        inner.extend_from_slice(perm_key_id);
+        inner.extend_from_slice(&temp_session_id.to_le_bytes());
+        inner.extend_from_slice(&expires_at.to_le_bytes());
+        assert_eq!(inner.len(), 40);
+
+        // random:int128 msg_id:long seqno:int msg_len:int, then the inner object
+        let mut message = random[..16].to_vec();
+        message.extend_from_slice(&msg_id.to_le_bytes());
+        message.extend_from_slice(&0u32.to_le_bytes());
+        message.extend_from_slice(&40u32.to_le_bytes());
+        message.extend_from_slice(&inner);
+
+        // MTProto 1.0 with x = 0, written out from the spec rather than through kdf_v1
+        let msg_key = &sha::sha1(&message)[4..20];
+        let sha1_a = sha::sha1(&[msg_key, &perm_key[..32]].concat());
+        let sha1_b = sha::sha1(&[&perm_key[32..48], msg_key, &perm_key[48..64]].concat());
+        let sha1_c = sha::sha1(&[&perm_key[64..96], msg_key].concat());
+        let sha1_d = sha::sha1(&[msg_key, &perm_key[96..128]].concat());
+        let aes_key = [&sha1_a[..8], &sha1_b[8..20], &sha1_c[4..16]].concat();
+        let aes_iv = [&sha1_a[8..20], &sha1_b[..8], &sha1_c[16..20], &sha1_d[..8]].concat();
+        let mut data = [&message[..], &random[16..]].concat();
+        aes::ige256_encrypt(&mut data, &aes_key, &aes_iv).unwrap();
+        let expected = [perm_key_id, msg_key, &data].concat();
+
+        let encrypted = mtproto::encrypt_bind_temp_auth_key_with_random(
+            &perm_key,
+            temp_key_id,
+            nonce,
+            expires_at,
+            temp_session_id,
+            msg_id,
+            random,
+        )
+        .unwrap();
+        assert_eq!(encrypted, expected);
+
+        // Field by field after decryption
+        let decrypted = mtproto::decrypt_message_v1(&perm_key, &encrypted, 0).unwrap();
+        let mut reader = TlReader(&decrypted);
+        assert_eq!(reader.take(16), &random[..16]);
+        assert_eq!(reader.take(8), msg_id.to_le_bytes());
+        assert_eq!(reader.u32(), 0);
+        assert_eq!(reader.u32(), 40);
+        assert_eq!(reader.u32(), 0x75a3f765);
+        assert_eq!(reader.take(8), nonce.to_le_bytes());
+        assert_eq!(reader.take(8), temp_key_id.to_le_bytes());
+        assert_eq!(reader.take(8), perm_key_id);
+        assert_eq!(reader.take(8), temp_session_id.to_le_bytes());
+        assert_eq!(reader.take(4), expires_at.to_le_bytes());
+        assert!(reader.0.is_empty());
+
+        let encrypted =
+            mtproto::encrypt_bind_temp_auth_key(&perm_key, temp_key_id, nonce, 1700086400, temp_session_id, None).unwrap();
+        assert_eq!(encrypted.len(), 104);
+        assert_eq!(
+            mtproto::encrypt_bind_temp_auth_key(&perm_key[1..], temp_key_id, nonce, 0, temp_session_id, Some(4)),
+            Err(Error::InvalidAuthKeyLength(255))
+        );
+    }
+
+    const TELEGRAM_DH_PRIME: &str = "C71CAEB9C6B1C9048E6C522F70F13F73980D40238E3E21C14934D037563D930F48198A0AA7C14058229493D22530F4DBFA336F6E0AC925139543AED44CCE7C3720FD51F69458705AC68CD4FE6B6B13ABDC9746512969328454F18FAF8C595F642477FE96BB2A941D5BCD1D4AC8CC49880708FA9B378E3C4F3A9060BEE67CF9A4A4A695811051907E162753B56B0F6B410DBA74D8A84B2A14B3144E0EF1284754FD17ED950D5965B4B9DD46582DB1178D169C6BC465B0D6FF9CA3928FEF5B9AE4E418FC15E83EBEA0F87FA9FF5EED70050DED2849F47BF959D956850CE929851F0D8115F635B105EE2E4E15D04B2454BF6F4FADF034B10403119CD8E3B92FCC5B";
+
+    #[test]
+    fn test_dh_params() {
+        let p = BigUint::parse_bytes(TELEGRAM_DH_PRIME.as_bytes(), 16).unwrap();
+        let dh_prime = p.to_bytes_be();
+        assert!(dh::is_safe_prime(&p));
+        assert!(!dh::is_safe_prime(&(&p + 2u32)));
+
+        dh::check_dh_prime(&dh_prime).unwrap();
+        dh::check_dh_params(&dh_prime, 3).unwrap();
+        assert_eq!(dh::check_dh_params(&dh_prime, 2), Err(Error::InvalidDhGenerator(2)));
+        assert_eq!(dh::check_dh_params(&dh_prime, 8), Err(Error::InvalidDhGenerator(8)));
+
+        // Not prime, or not 2048 bits
+        assert_eq!(dh::check_dh_prime(&(&p + 2u32).to_bytes_be()), Err(Error::InvalidDhPrime));
+        assert_eq!(dh::check_dh_prime(&dh_prime[1..]), Err(Error::InvalidDhPrime));
+
+        assert_eq!(dh::check_g_a(&[2], &dh_prime), Err(Error::DhValueOutOfRange));
+        assert_eq!(dh::check_g_a(&(&p - 2u32).to_bytes_be(), &dh_prime), Err(Error::DhValueOutOfRange));
+    }
+
+    #[test]
+    fn test_dh_exchange() {
+        let dh_prime = BigUint::parse_bytes(TELEGRAM_DH_PRIME.as_bytes(), 16).unwrap().to_bytes_be();
+        let (a, b) = ([0x5au8; 256], [0xa5u8; 256]);
+
+        let g_a = dh::compute_g_b(3, &a, &dh_prime).unwrap();
+        let g_b = dh::compute_g_b(3, &b, &dh_prime).unwrap();
+        dh::check_g_a(&g_a, &dh_prime).unwrap();
+
+        let auth_key = dh::compute_auth_key(&g_a, &b, &dh_prime).unwrap();
+        assert_eq!(auth_key, dh::compute_auth_key(&g_b, &a, &dh_prime).unwrap());
+
+        let expected = BigUint::from(3u32)
+            .modpow(&(BigUint::from_bytes_be(&a) * BigUint::from_bytes_be(&b)), &BigUint::from_bytes_be(&dh_prime));
+        assert_eq!(BigUint::from_bytes_be(&auth_key), expected);
+
+        assert_eq!(dh::compute_auth_key(&[1], &b, &dh_prime), Err(Error::DhValueOutOfRange));
+    }
+
+    fn tl_bytes(out: &mut Vec<u8>, data: &[u8]) {
+        match data.len() {
+            len if len < 254 => out.push(len as u8),
+            len => out.extend_from_slice(&((len as u32) << 8 | 254).to_le_bytes()),
+        }
+        out.extend_from_slice(data);
+        out.resize(out.len().next_multiple_of(4), 0);
+    }
+
+    /// Minimal TL reader for the server side of the handshake tests
+    struct TlReader<'a>(&'a [u8]);
+
+    impl<'a> TlReader<'a> {
+        fn take(&mut self, len: usize) -> &'a [u8] {
+            let (head, tail) = self.0.split_at(len);
+            self.0 = tail;
+            head
+        }
+
+        fn u32(&mut self) -> u32 {
+            u32::from_le_bytes(self.take(4).try_into().unwrap())
+        }
+
+        fn bytes(&mut self) -> &'a [u8] {
+            let (header, len) = match self.0[0] {
+                254 => (4, (self.u32() >> 8) as usize),
+                _ => (1, self.take(1)[0] as usize),
+            };
+            let data = self.take(len);
+            self.take((header + len).next_multiple_of(4) - header - len);
+            data
+        }
+    }
+
+    fn plain_message(body: &[u8]) -> Vec<u8> {
+        let msg_id = (1_700_000_000i64 << 32) | 1;
+        [&[0u8; 8][..], &msg_id.to_le_bytes(), &(body.len() as u32).to_le_bytes(), body].concat()
+    }
+
+    /// Undo RSA_PAD with the fixture private key, returning data_with_padding
+    fn rsa_pad_decrypt(encrypted: &[u8]) -> Vec<u8> {
+        let decrypted = rsa_test_key_decrypt(encrypted);
+        let (temp_key_xor, aes_encrypted) = decrypted.split_at(32);
+        let hash = sha::sha256(aes_encrypted);
+        let temp_key: Vec<u8> = temp_key_xor.iter().zip(hash).map(|(a, b)| a ^ b).collect();
+        let mut data_with_hash = aes_encrypted.to_vec();
+        aes::ige256_decrypt(&mut data_with_hash, &temp_key, &[0u8; 32]).unwrap();
+        let mut data_with_padding = data_with_hash[..192].to_vec();
+        data_with_padding.reverse();
+        data_with_padding
+    }
+
+    #[test]
+    fn test_auth_key_handshake() {
+        use tgcrypto::handshake::{AuthKeyHandshake, KeyKind, Step};
+        use tgcrypto::mtproto::DhGenStatus;
+
+        let _registry = lock_rsa_registry();
+        let fingerprint = rsa::add_public_key(include_str!("fixtures/rsa_test_key.pem")).unwrap();
+        let dh_prime = BigUint::parse_bytes(TELEGRAM_DH_PRIME.as_bytes(), 16).unwrap().to_bytes_be();
+        let now = 1_700_000_000;
+        let server_nonce = [0x77u8; 16];
+        let a = [0x42u8; 256];
+
+        let mut handshake = AuthKeyHandshake::new(2, KeyKind::Temporary { expires_in: 3600 });
+        let req_pq = handshake.start(now);
+        assert_eq!(req_pq[..8], [0u8; 8]);
+        let mut msg_ids = vec![i64::from_le_bytes(req_pq[8..16].try_into().unwrap())];
+        assert_eq!(msg_ids[0] >> 32, now);
+        let mut reader = TlReader(&req_pq[20..]);
+        assert_eq!(reader.u32(), 0xbe7e8ef1);
+        let nonce = reader.take(16).to_vec();
+
+        // resPQ offering an unknown key first, with the pq from the documentation example
+        let mut res_pq = 0x05162463u32.to_le_bytes().to_vec();
+        res_pq.extend_from_slice(&nonce);
+        res_pq.extend_from_slice(&server_nonce);
+        tl_bytes(&mut res_pq, &0x17ED48941A08F981u64.to_be_bytes());
+        res_pq.extend_from_slice(&0x1cb5c415u32.to_le_bytes());
+        res_pq.extend_from_slice(&2u32.to_le_bytes());
+        res_pq.extend_from_slice(&1i64.to_le_bytes());
+        res_pq.extend_from_slice(&fingerprint.to_le_bytes());
+
+        let Step::Send(req_dh_params) = handshake.feed(&plain_message(&res_pq), now).unwrap() else {
+            panic!("expected req_DH_params");
+        };
+        msg_ids.push(i64::from_le_bytes(req_dh_params[8..16].try_into().unwrap()));
+        let mut reader = TlReader(&req_dh_params[20..]);
+        assert_eq!(reader.u32(), 0xd712e4be);
+        assert_eq!(reader.take(16), nonce);
+        assert_eq!(reader.take(16), server_nonce);
+        assert_eq!(reader.bytes(), 0x494C553Bu32.to_be_bytes());
+        assert_eq!(reader.bytes(), 0x53911073u32.to_be_bytes());
+        assert_eq!(reader.take(8), fingerprint.to_le_bytes());
+        let inner = rsa_pad_decrypt(reader.bytes());
+
+        let mut reader = TlReader(&inner);
+        assert_eq!(reader.u32(), 0x56fddf88);
+        assert_eq!(reader.bytes(), 0x17ED48941A08F981u64.to_be_bytes());
+        reader.bytes();
+        reader.bytes();
+        assert_eq!(reader.take(16), nonce);
+        assert_eq!(reader.take(16), server_nonce);
+        let new_nonce: [u8; 32] = reader.take(32).try_into().unwrap();
+        assert_eq!(reader.u32(), 2);
+        assert_eq!(reader.u32(), 3600);
+
+        let mut inner_data = 0xb5890dbau32.to_le_bytes().to_vec();
+        inner_data.extend_from_slice(&nonce);
+        inner_data.extend_from_slice(&server_nonce);
+        inner_data.extend_from_slice(&3u32.to_le_bytes());
+        tl_bytes(&mut inner_data, &dh_prime);
+        tl_bytes(&mut inner_data, &dh::compute_g_b(3, &a, &dh_prime).unwrap());
+        inner_data.extend_from_slice(&((now + 100) as u32).to_le_bytes());
+
+        let mut server_dh_params = 0xd0e8075cu32.to_le_bytes().to_vec();
+        server_dh_params.extend_from_slice(&nonce);
+        server_dh_params.extend_from_slice(&server_nonce);
+        tl_bytes(&mut server_dh_params, &encrypt_dh_answer(&inner_data, &new_nonce, &server_nonce));
+        let mut answer = plain_message(&server_dh_params);
+
+        // Answer dh_gen_retry once, then dh_gen_ok
+        let mut retry_id = 0;
+        for (constructor, status) in [(0x46dc1fb9u32, DhGenStatus::Retry), (0x3bcbf734u32, DhGenStatus::Ok)] {
+            let Step::Send(set_client_dh_params) = handshake.feed(&answer, now).unwrap() else {
+                panic!("expected set_client_DH_params");
+            };
+            msg_ids.push(i64::from_le_bytes(set_client_dh_params[8..16].try_into().unwrap()));
+            assert_eq!(msg_ids.last().unwrap() >> 32, now + 100);
+            let mut reader = TlReader(&set_client_dh_params[20..]);
+            assert_eq!(reader.u32(), 0xf5045f1f);
+            assert_eq!(reader.take(16), nonce);
+            assert_eq!(reader.take(16), server_nonce);
+            let data = mtproto::decrypt_server_dh_inner_data(reader.bytes(), &new_nonce, &server_nonce).unwrap();
+
+            let mut reader = TlReader(&data);
+            assert_eq!(reader.u32(), 0x6643b654);
+            assert_eq!(reader.take(16), nonce);
+            assert_eq!(reader.take(16), server_nonce);
+            assert_eq!(i64::from_le_bytes(reader.take(8).try_into().unwrap()), retry_id);
+            let auth_key = dh::compute_auth_key(reader.bytes(), &a, &dh_prime).unwrap();
+            retry_id = i64::from_le_bytes(mtproto::auth_key_aux_hash(&auth_key));
+
+            let mut dh_gen = constructor.to_le_bytes().to_vec();
+            dh_gen.extend_from_slice(&nonce);
+            dh_gen.extend_from_slice(&server_nonce);
+            dh_gen.extend_from_slice(&mtproto::new_nonce_hash(&new_nonce, &auth_key, status));
+            answer = plain_message(&dh_gen);
+
+            if status == DhGenStatus::Ok {
+                let Step::Done(key) = handshake.feed(&answer, now).unwrap() else {
+                    panic!("expected dh_gen_ok to finish the handshake");
+                };
+                assert_eq!(key.auth_key, auth_key);
+                assert_eq!(key.auth_key_id, mtproto::auth_key_id(&auth_key));
+                assert_eq!(key.server_salt, mtproto::server_salt(&new_nonce, &server_nonce));
+                assert_eq!(key.time_offset, 100);
+                assert_eq!(key.expires_at, Some(now + 100 + 3600));
+            }
+        }
+
+        assert_eq!(handshake.feed(&answer, now), Err(Error::InvalidHandshakeState));
+        assert!(rsa::remove_public_key(fingerprint));
+
+        // Client msg_ids carry non-zero low bits divisible by 4 and only increase
+        assert_eq!(msg_ids.len(), 4);
+        for msg_id in &msg_ids {
+            assert_ne!(msg_id & 0xffffffff, 0);
+            assert_eq!(msg_id % 4, 0);
+        }
+        assert!(msg_ids.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    #[test]
+    fn test_auth_key_handshake_errors() {
+        use tgcrypto::handshake::{AuthKeyHandshake, KeyKind};
+
+        // Extreme clocks clamp the msg_id time instead of overflowing
+        for (now, time) in [(i64::MIN, 0), (-1, 0), (i64::MAX, i32::MAX as i64)] {
+            let req_pq = AuthKeyHandshake::new(2, KeyKind::Permanent).start(now);
+            let msg_id = i64::from_le_bytes(req_pq[8..16].try_into().unwrap());
+            assert_eq!(msg_id >> 32, time);
+            assert_eq!(msg_id % 4, 0);
+        }
+
+        let mut handshake = AuthKeyHandshake::new(2, KeyKind::Permanent);
+        assert_eq!(handshake.feed(&plain_message(&[0u8; 4]), 0), Err(Error::InvalidHandshakeState));
+
+        let req_pq = handshake.start(0);
+        let res_pq = |nonce: &[u8], fingerprint: i64| {
+            let mut res_pq = 0x05162463u32.to_le_bytes().to_vec();
+            res_pq.extend_from_slice(nonce);
+            res_pq.extend_from_slice(&[0x77u8; 16]);
+            tl_bytes(&mut res_pq, &0x17ED48941A08F981u64.to_be_bytes());
+            res_pq.extend_from_slice(&0x1cb5c415u32.to_le_bytes());
+            res_pq.extend_from_slice(&1u32.to_le_bytes());
+            res_pq.extend_from_slice(&fingerprint.to_le_bytes());
+            plain_message(&res_pq)
+        };
+
+        assert_eq!(handshake.feed(&res_pq(&req_pq[24..40], 1), 0), Err(Error::NoKnownFingerprint));
+        // Errors reset the handshake
+        assert!(!handshake.is_started());
+
+        let req_pq = handshake.start(0);
+        assert_eq!(
+            handshake.feed(&res_pq(&[0u8; 16], -4344800451088585951), 0),
+            Err(Error::NonceMismatch)
+        );
+
+        let req_pq_2 = handshake.start(0);
+        assert_ne!(req_pq[24..40], req_pq_2[24..40]);
+        let mut encrypted = res_pq(&req_pq_2[24..40], -4344800451088585951);
+        encrypted[0] = 1;
+        assert_eq!(handshake.feed(&encrypted, 0), Err(Error::AuthKeyIdMismatch));
+
+        handshake.start(0);
+        assert_eq!(handshake.feed(&plain_message(&0x12345678u32.to_le_bytes()), 0), Err(Error::UnexpectedConstructor(0x12345678)));
+        handshake.start(0);
+        assert_eq!(handshake.feed(&[0u8; 19], 0), Err(Error::MalformedTl));
     }
 }
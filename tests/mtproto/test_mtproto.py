@@ -0,0 +1,248 @@
+#  Pyrogram - Telegram MTProto API Client Library for Python
+#  Copyright (C) 2017-present Dan <https://github.com/delivrance>
+#
+#  This file is part of Pyrogram.
+#
+#  Pyrogram is free software: you can redistribute it and/or modify
+#  it under the terms of the GNU Lesser General Public License as published
+#  by the Free Software Foundation, either version 3 of the License, or
+#  (at your option) any later version.
+#
+#  Pyrogram is distributed in the hope that it will be useful,
+#  but WITHOUT ANY WARRANTY; without even the implied warranty of
+#  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+#  GNU Lesser General Public License for more details.
+#
+#  You should have received a copy of the GNU Lesser General Public License
+#  along with Pyrogram.  If not, see <http://www.gnu.org/licenses/>.
+
+import hashlib
+import os
+import struct
+import time
+import unittest
+
+import tgcrypto
+
+
+def kdf(auth_key: bytes, msg_key: bytes, x: int) -> tuple:
+    sha256_a = hashlib.sha256(msg_key + auth_key[x: x + 36]).digest()
+    sha256_b = hashlib.sha256(auth_key[x + 40:x + 76] + msg_key).digest()
+
+    aes_key = sha256_a[:8] + sha256_b[8:24] + sha256_a[24:32]
+    aes_iv = sha256_b[:8] + sha256_a[8:24] + sha256_b[24:32]
+
+    return aes_key, aes_iv
+
+
+def kdf_v1(auth_key: bytes, msg_key: bytes, x: int) -> tuple:
+    sha1_a = hashlib.sha1(msg_key + auth_key[x:x + 32]).digest()
+    sha1_b = hashlib.sha1(auth_key[x + 32:x + 48] + msg_key + auth_key[x + 48:x + 64]).digest()
+    sha1_c = hashlib.sha1(auth_key[x + 64:x + 96] + msg_key).digest()
+    sha1_d = hashlib.sha1(msg_key + auth_key[x + 96:x + 128]).digest()
+
+    aes_key = sha1_a[:8] + sha1_b[8:20] + sha1_c[4:16]
+    aes_iv = sha1_a[8:20] + sha1_b[:8] + sha1_c[16:20] + sha1_d[:8]
+
+    return aes_key, aes_iv
+
+
+class TestMTProto2Message(unittest.TestCase):
+    def test_matches_python_implementation(self):
+        auth_key = os.urandom(256)
+        plaintext = os.urandom(64)
+
+        packet = tgcrypto.encrypt_message(auth_key, plaintext, 0)
+        self.assertEqual(packet[:8], hashlib.sha1(auth_key).digest()[-8:])
+
+        msg_key = packet[8:24]
+        aes_key, aes_iv = kdf(auth_key, msg_key, 0)
+        data = tgcrypto.ige256_decrypt(packet[24:], aes_key, aes_iv)
+
+        self.assertEqual(data[:64], plaintext)
+        self.assertEqual(hashlib.sha256(auth_key[88:120] + data).digest()[8:24], msg_key)
+        self.assertEqual(tgcrypto.decrypt_message(auth_key, packet, 0), data)
+
+    def test_server_message(self):
+        auth_key = os.urandom(256)
+        plaintext = os.urandom(100)
+
+        packet = tgcrypto.encrypt_message(auth_key, plaintext, 8)
+        self.assertEqual(tgcrypto.decrypt_message(auth_key, packet, 8)[:100], plaintext)
+
+    def test_quick_ack(self):
+        auth_key = os.urandom(256)
+
+        packet, token = tgcrypto.encrypt_message_quick_ack(auth_key, os.urandom(64), 0)
+        data = tgcrypto.decrypt_message(auth_key, packet, 0)
+        digest = hashlib.sha256(auth_key[88:120] + data).digest()
+        self.assertEqual(token, int.from_bytes(digest[:4], "little") | 1 << 31)
+
+    def test_msg_key_mismatch(self):
+        auth_key = os.urandom(256)
+        packet = bytearray(tgcrypto.encrypt_message(auth_key, os.urandom(32), 8))
+        packet[-1] ^= 0xFF
+
+        with self.assertRaisesRegex(ValueError, r"msg_key mismatch"):
+            tgcrypto.decrypt_message(auth_key, bytes(packet), 8)
+
+    def test_invalid_auth_key_size(self):
+        with self.assertRaisesRegex(ValueError, r"Auth key must be 256 bytes"):
+            tgcrypto.encrypt_message(os.urandom(255), os.urandom(32), 0)
+
+
+class TestMTProto1Message(unittest.TestCase):
+    def test_kdf(self):
+        auth_key, msg_key = os.urandom(256), os.urandom(16)
+
+        for x in (0, 8):
+            self.assertEqual(tgcrypto.kdf_v1(auth_key, msg_key, x), kdf_v1(auth_key, msg_key, x))
+
+    def test_roundtrip(self):
+        auth_key, body = os.urandom(256), os.urandom(20)
+        plaintext = os.urandom(28) + len(body).to_bytes(4, "little") + body
+
+        packet = tgcrypto.encrypt_message_v1(auth_key, plaintext, 8)
+        self.assertEqual(packet[8:24], hashlib.sha1(plaintext).digest()[4:20])
+        self.assertEqual(tgcrypto.decrypt_message_v1(auth_key, packet, 8), plaintext)
+
+    def test_secret_roundtrip(self):
+        auth_key, payload = os.urandom(256), os.urandom(45)
+        plaintext = len(payload).to_bytes(4, "little") + payload
+
+        packet = tgcrypto.encrypt_secret_message_v1(auth_key, payload)
+        msg_key = packet[8:24]
+        self.assertEqual(msg_key, hashlib.sha1(plaintext).digest()[4:20])
+        self.assertEqual(len(packet), 24 + 64)
+
+        aes_key, aes_iv = kdf_v1(auth_key, msg_key, 0)
+        self.assertEqual(tgcrypto.ige256_decrypt(packet[24:], aes_key, aes_iv)[:len(plaintext)], plaintext)
+        self.assertEqual(tgcrypto.decrypt_secret_message_v1(auth_key, packet), payload)
+        self.assertEqual(tgcrypto.decrypt_secret_message_v1(auth_key, packet, 0), payload)
+
+
+class TestServerMessage(unittest.TestCase):
+    def pack(self, session_id: bytes, msg_id: int, body: bytes) -> bytes:
+        return (
+            os.urandom(8) + session_id + msg_id.to_bytes(8, "little")
+            + (1).to_bytes(4, "little") + len(body).to_bytes(4, "little") + body
+        )
+
+    def test_valid(self):
+        auth_key, session_id, body = os.urandom(256), os.urandom(8), os.urandom(40)
+        msg_id = (int(time.time()) << 32) | 1
+
+        packet = tgcrypto.encrypt_message(auth_key, self.pack(session_id, msg_id, body), 8)
+        salt, received_msg_id, seq_no, received_body = tgcrypto.decrypt_server_message(auth_key, packet, session_id)
+
+        self.assertEqual((received_msg_id, seq_no, received_body), (msg_id, 1, body))
+
+    def test_session_mismatch(self):
+        auth_key, session_id = os.urandom(256), os.urandom(8)
+        msg_id = (int(time.time()) << 32) | 1
+
+        packet = tgcrypto.encrypt_message(auth_key, self.pack(session_id, msg_id, b""), 8)
+        with self.assertRaisesRegex(ValueError, r"session_id mismatch"):
+            tgcrypto.decrypt_server_message(auth_key, packet, os.urandom(8))
+
+    def test_msg_id_out_of_window(self):
+        auth_key, session_id = os.urandom(256), os.urandom(8)
+        msg_id = ((int(time.time()) - 3600) << 32) | 1
+
+        packet = tgcrypto.encrypt_message(auth_key, self.pack(session_id, msg_id, b""), 8)
+        with self.assertRaisesRegex(ValueError, r"Invalid msg_id"):
+            tgcrypto.decrypt_server_message(auth_key, packet, session_id)
+
+        # A matching server time offset brings it back into the window
+        tgcrypto.decrypt_server_message(auth_key, packet, session_id, -3600)
+
+
+def tmp_aes_key_iv(new_nonce: bytes, server_nonce: bytes) -> tuple:
+    new_server = hashlib.sha1(new_nonce + server_nonce).digest()
+    server_new = hashlib.sha1(server_nonce + new_nonce).digest()
+    new_new = hashlib.sha1(new_nonce + new_nonce).digest()
+
+    return new_server + server_new[:12], server_new[12:] + new_new + new_nonce[:4]
+
+
+class TestServerDHInnerData(unittest.TestCase):
+    def test_tmp_aes_key_iv(self):
+        new_nonce, server_nonce = os.urandom(32), os.urandom(16)
+        self.assertEqual(tgcrypto.tmp_aes_key_iv(new_nonce, server_nonce), tmp_aes_key_iv(new_nonce, server_nonce))
+
+    def test_decrypt(self):
+        new_nonce, server_nonce = os.urandom(32), os.urandom(16)
+        data = os.urandom(564)
+
+        answer = hashlib.sha1(data).digest() + data
+        answer += os.urandom(-len(answer) % 16)
+        encrypted_answer = tgcrypto.ige256_encrypt(answer, *tmp_aes_key_iv(new_nonce, server_nonce))
+
+        self.assertEqual(tgcrypto.decrypt_server_dh_inner_data(encrypted_answer, new_nonce, server_nonce), data)
+
+        with self.assertRaisesRegex(ValueError, r"Server_DH_inner_data hash mismatch"):
+            tgcrypto.decrypt_server_dh_inner_data(encrypted_answer, os.urandom(32), server_nonce)
+
+
+class TestClientDHInnerData(unittest.TestCase):
+    def test_encrypt(self):
+        new_nonce, server_nonce = os.urandom(32), os.urandom(16)
+        data = os.urandom(376)
+
+        encrypted_data = tgcrypto.encrypt_client_dh_inner_data(data, new_nonce, server_nonce)
+        self.assertEqual(len(encrypted_data), 400)
+
+        decrypted = tgcrypto.ige256_decrypt(encrypted_data, *tmp_aes_key_iv(new_nonce, server_nonce))
+        self.assertEqual(decrypted[:396], hashlib.sha1(data).digest() + data)
+
+
+class TestNewNonceHash(unittest.TestCase):
+    def test_new_nonce_hash(self):
+        new_nonce, auth_key = os.urandom(32), os.urandom(256)
+        auth_key_aux_hash = hashlib.sha1(auth_key).digest()[:8]
+        self.assertEqual(tgcrypto.auth_key_aux_hash(auth_key), auth_key_aux_hash)
+
+        for number in (1, 2, 3):
+            expected = hashlib.sha1(new_nonce + bytes([number]) + auth_key_aux_hash).digest()[4:]
+            self.assertEqual(tgcrypto.new_nonce_hash(new_nonce, auth_key, number), expected)
+            tgcrypto.check_new_nonce_hash(new_nonce, auth_key, number, expected)
+
+    def test_mismatch(self):
+        new_nonce, auth_key = os.urandom(32), os.urandom(256)
+        hash = tgcrypto.new_nonce_hash(new_nonce, auth_key, 1)
+
+        with self.assertRaisesRegex(ValueError, "new_nonce_hash mismatch"):
+            tgcrypto.check_new_nonce_hash(new_nonce, auth_key, 2, hash)
+
+        with self.assertRaisesRegex(ValueError, "1, 2 or 3"):
+            tgcrypto.new_nonce_hash(new_nonce, auth_key, 4)
+
+    def test_server_salt(self):
+        new_nonce, server_nonce = os.urandom(32), os.urandom(16)
+        expected = bytes(a ^ b for a, b in zip(new_nonce[:8], server_nonce[:8]))
+        self.assertEqual(tgcrypto.server_salt(new_nonce, server_nonce), int.from_bytes(expected, "little", signed=True))
+
+
+class TestBindTempAuthKey(unittest.TestCase):
+    def test_encrypted_message(self):
+        perm_key, msg_id = os.urandom(256), 0x6553f10000000004
+        encrypted = tgcrypto.encrypt_bind_temp_auth_key(perm_key, -5, 42, 1700086400, 7, msg_id=msg_id)
+        self.assertEqual(len(encrypted), 104)
+
+        message = tgcrypto.decrypt_message_v1(perm_key, encrypted, 0)
+        perm_key_id = hashlib.sha1(perm_key).digest()[-8:]
+        inner = struct.pack("<Iqq", 0x75a3f765, 42, -5) + perm_key_id + struct.pack("<qi", 7, 1700086400)
+        self.assertEqual(message[16:], struct.pack("<qII", msg_id, 0, 40) + inner)
+
+    def test_random_msg_id(self):
+        perm_key = os.urandom(256)
+        first = tgcrypto.encrypt_bind_temp_auth_key(perm_key, 1, 2, 3, 4)
+        second = tgcrypto.encrypt_bind_temp_auth_key(perm_key, 1, 2, 3, 4)
+        self.assertNotEqual(first, second)
+
+        with self.assertRaisesRegex(ValueError, "256 bytes"):
+            tgcrypto.encrypt_bind_temp_auth_key(perm_key[:128], 1, 2, 3, 4)
+
+
+if __name__ == "__main__":
+    unittest.main()
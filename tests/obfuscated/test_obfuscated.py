@@ -0,0 +1,109 @@
+#  Pyrogram - Telegram MTProto API Client Library for Python
+#  Copyright (C) 2017-present Dan <https://github.com/delivrance>
+#
+#  This file is part of Pyrogram.
+#
+#  Pyrogram is free software: you can redistribute it and/or modify
+#  it under the terms of the GNU Lesser General Public License as published
+#  by the Free Software Foundation, either version 3 of the License, or
+#  (at your option) any later version.
+#
+#  Pyrogram is distributed in the hope that it will be useful,
+#  but WITHOUT ANY WARRANTY; without even the implied warranty of
+#  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+#  GNU Lesser General Public License for more details.
+#
+#  You should have received a copy of the GNU Lesser General Public License
+#  along with Pyrogram.  If not, see <http://www.gnu.org/licenses/>.
+
+import hashlib
+import os
+import unittest
+
+import tgcrypto
+
+
+class TestObfuscated2(unittest.TestCase):
+    def server_streams(self, init_packet: bytes, secret: bytes = None) -> tuple:
+        key_iv = init_packet[8:56]
+        reversed_key_iv = key_iv[::-1]
+        keys = [key_iv[:32], reversed_key_iv[:32]]
+
+        if secret is not None:
+            keys = [hashlib.sha256(key + secret).digest() for key in keys]
+
+        return tgcrypto.CtrStream(keys[0], key_iv[32:]), tgcrypto.CtrStream(keys[1], reversed_key_iv[32:])
+
+    def test_init_packet(self):
+        for secret in (None, os.urandom(16)):
+            client = tgcrypto.Obfuscated2(b"\xee" * 4, 2, secret)
+            decryptor, encryptor = self.server_streams(client.init_packet, secret)
+
+            header = decryptor.process(client.init_packet)
+            self.assertEqual(header[56:62], b"\xee" * 4 + (2).to_bytes(2, "little"))
+            self.assertEqual(client.encryptor.tell(), 64)
+
+            data = os.urandom(100)
+            self.assertEqual(decryptor.process(client.encryptor.process(data)), data)
+            self.assertEqual(client.decryptor.process(encryptor.process(data)), data)
+
+    def test_invalid_tag(self):
+        with self.assertRaisesRegex(ValueError, r"Unknown protocol tag"):
+            tgcrypto.Obfuscated2(b"\x00" * 4, 2)
+
+
+class TestObfuscated2Server(unittest.TestCase):
+    SECRETS = [os.urandom(16), b"", b"\xdd" + os.urandom(16)]
+
+    def test_accept(self):
+        for index, secret in enumerate(self.SECRETS):
+            client = tgcrypto.Obfuscated2(b"\xdd" * 4, -2, secret or None)
+            server = tgcrypto.Obfuscated2Server(client.init_packet, self.SECRETS)
+
+            self.assertEqual(server.secret_index, index)
+            self.assertEqual(server.tag, b"\xdd" * 4)
+            self.assertEqual(server.dc_id, -2)
+
+            data = os.urandom(100)
+            self.assertEqual(server.decryptor.process(client.encryptor.process(data)), data)
+            self.assertEqual(client.decryptor.process(server.encryptor.process(data)), data)
+
+    def test_unknown_secret(self):
+        client = tgcrypto.Obfuscated2(b"\xef" * 4, 2, os.urandom(16))
+
+        with self.assertRaisesRegex(ValueError, r"No MTProxy secret matches"):
+            tgcrypto.Obfuscated2Server(client.init_packet, self.SECRETS[:1])
+
+
+class TestFakeTlsServer(unittest.TestCase):
+    SECRET = b"\xee" + os.urandom(16) + b"example.com"
+
+    def test_handshake(self):
+        client = tgcrypto.FakeTlsClient(self.SECRET, b"\xee" * 4, 5)
+        server = tgcrypto.FakeTlsServer(client.client_hello, [os.urandom(16), self.SECRET])
+
+        self.assertEqual(server.secret_index, 1)
+        self.assertEqual(client.verify_server_hello(server.server_hello), len(server.server_hello))
+        self.assertIsNone(server.dc_id)
+
+        self.assertEqual(server.decrypt(client.encrypt(b"ping")), b"ping")
+        self.assertEqual(server.tag, b"\xee" * 4)
+        self.assertEqual(server.dc_id, 5)
+        self.assertEqual(client.decrypt(server.encrypt(b"pong")), b"pong")
+
+    def test_data_after_client_hello(self):
+        client = tgcrypto.FakeTlsClient(self.SECRET, b"\xee" * 4, 5)
+        server = tgcrypto.FakeTlsServer(client.client_hello + client.encrypt(b"ping"), [self.SECRET])
+
+        self.assertEqual(server.decrypt(b""), b"ping")
+        self.assertEqual(server.dc_id, 5)
+
+    def test_unknown_secret(self):
+        client = tgcrypto.FakeTlsClient(self.SECRET, b"\xee" * 4, 5)
+
+        with self.assertRaisesRegex(ValueError, r"No MTProxy secret matches"):
+            tgcrypto.FakeTlsServer(client.client_hello, [os.urandom(16)])
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,115 @@
+#  Pyrogram - Telegram MTProto API Client Library for Python
+#  Copyright (C) 2017-present Dan <https://github.com/delivrance>
+#
+#  This file is part of Pyrogram.
+#
+#  Pyrogram is free software: you can redistribute it and/or modify
+#  it under the terms of the GNU Lesser General Public License as published
+#  by the Free Software Foundation, either version 3 of the License, or
+#  (at your option) any later version.
+#
+#  Pyrogram is distributed in the hope that it will be useful,
+#  but WITHOUT ANY WARRANTY; without even the implied warranty of
+#  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+#  GNU Lesser General Public License for more details.
+#
+#  You should have received a copy of the GNU Lesser General Public License
+#  along with Pyrogram.  If not, see <http://www.gnu.org/licenses/>.
+
+import hashlib
+import os
+import unittest
+
+import tgcrypto
+
+FIXTURES = os.path.join(os.path.dirname(__file__), os.pardir, "fixtures")
+
+with open(os.path.join(FIXTURES, "rsa_test_key.pem")) as f:
+    TEST_KEY_PEM = f.read()
+
+with open(os.path.join(FIXTURES, "rsa_test_key_spki.pem")) as f:
+    TEST_KEY_SPKI_PEM = f.read()
+
+with open(os.path.join(FIXTURES, "rsa_test_key.hex")) as f:
+    TEST_KEY_N, TEST_KEY_D = (int(line, 16) for line in f.read().split())
+
+
+def tl_bytes(data: bytes) -> bytes:
+    header = bytes([len(data)]) if len(data) < 254 else b"\xfe" + len(data).to_bytes(3, "little")
+    padding = -(len(header) + len(data)) % 4
+    return header + data + bytes(padding)
+
+
+def decrypt(data: bytes) -> bytes:
+    return pow(int.from_bytes(data, "big"), TEST_KEY_D, TEST_KEY_N).to_bytes(256, "big")
+
+
+class TestKeyRegistry(unittest.TestCase):
+    def setUp(self):
+        self.fingerprint = tgcrypto.add_public_key(TEST_KEY_PEM)
+
+    def tearDown(self):
+        tgcrypto.remove_public_key(self.fingerprint)
+
+    def test_registry(self):
+        self.assertIn(-4344800451088585951, tgcrypto.list_public_keys())
+        self.assertIn(self.fingerprint, tgcrypto.list_public_keys())
+
+        self.assertTrue(tgcrypto.remove_public_key(self.fingerprint))
+        self.assertFalse(tgcrypto.remove_public_key(self.fingerprint))
+        with self.assertRaisesRegex(ValueError, r"Unknown fingerprint"):
+            tgcrypto.rsa_encrypt(b"data", self.fingerprint)
+
+    def test_test_dc_keys(self):
+        fingerprints = tgcrypto.add_test_dc_keys()
+        self.assertEqual(fingerprints, [-5595554452916591101])
+
+        for fingerprint in fingerprints:
+            self.assertTrue(tgcrypto.remove_public_key(fingerprint))
+
+    def test_invalid_pem(self):
+        with self.assertRaisesRegex(ValueError, r"Invalid RSA public key"):
+            tgcrypto.add_public_key("not a key")
+
+    def test_rsa_pad(self):
+        data = os.urandom(144)
+        key_aes_encrypted = decrypt(tgcrypto.rsa_pad_encrypt(data, self.fingerprint))
+
+        temp_key_xor, aes_encrypted = key_aes_encrypted[:32], key_aes_encrypted[32:]
+        digest = hashlib.sha256(aes_encrypted).digest()
+        temp_key = bytes(a ^ b for a, b in zip(temp_key_xor, digest))
+
+        data_with_hash = tgcrypto.ige256_decrypt(aes_encrypted, temp_key, bytes(32))
+        data_with_padding = data_with_hash[:192][::-1]
+        self.assertEqual(data_with_padding[:144], data)
+        self.assertEqual(data_with_hash[192:], hashlib.sha256(temp_key + data_with_padding).digest())
+
+    def test_sha1_padding(self):
+        data = os.urandom(100)
+        decrypted = decrypt(tgcrypto.rsa_encrypt(data, self.fingerprint, padding="sha1"))
+        self.assertEqual(decrypted[:121], b"\x00" + hashlib.sha1(data).digest() + data)
+
+        with self.assertRaisesRegex(ValueError, r"at most 235 bytes"):
+            tgcrypto.rsa_encrypt(os.urandom(236), self.fingerprint, padding="sha1")
+
+    def test_raw_exceeds_modulus(self):
+        with self.assertRaisesRegex(ValueError, r"smaller than the RSA modulus"):
+            tgcrypto.rsa_encrypt(b"\xff" * 256, self.fingerprint)
+
+
+class TestFingerprint(unittest.TestCase):
+    def test_matches_python_implementation(self):
+        serialized = tl_bytes(TEST_KEY_N.to_bytes(256, "big")) + tl_bytes((65537).to_bytes(3, "big"))
+        expected = int.from_bytes(hashlib.sha1(serialized).digest()[-8:], "little", signed=True)
+
+        self.assertEqual(tgcrypto.rsa_fingerprint(TEST_KEY_PEM), expected)
+        self.assertEqual(tgcrypto.rsa_fingerprint(TEST_KEY_SPKI_PEM), expected)
+
+    def test_spki_registration(self):
+        fingerprint = tgcrypto.add_public_key(TEST_KEY_SPKI_PEM)
+        self.assertEqual(fingerprint, tgcrypto.rsa_fingerprint(TEST_KEY_PEM))
+        self.assertTrue(tgcrypto.remove_public_key(fingerprint))
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,76 @@
+#  Pyrogram - Telegram MTProto API Client Library for Python
+#  Copyright (C) 2017-present Dan <https://github.com/delivrance>
+#
+#  This file is part of Pyrogram.
+#
+#  Pyrogram is free software: you can redistribute it and/or modify
+#  it under the terms of the GNU Lesser General Public License as published
+#  by the Free Software Foundation, either version 3 of the License, or
+#  (at your option) any later version.
+#
+#  Pyrogram is distributed in the hope that it will be useful,
+#  but WITHOUT ANY WARRANTY; without even the implied warranty of
+#  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+#  GNU Lesser General Public License for more details.
+#
+#  You should have received a copy of the GNU Lesser General Public License
+#  along with Pyrogram.  If not, see <http://www.gnu.org/licenses/>.
+
+import os
+import struct
+import unittest
+import zlib
+
+import tgcrypto
+
+
+class TestTransportCodec(unittest.TestCase):
+    TRANSPORTS = ("abridged", "intermediate", "padded_intermediate", "full")
+
+    def test_roundtrip(self):
+        packets = [os.urandom(40), os.urandom(1024), os.urandom(8)]
+
+        for transport in self.TRANSPORTS:
+            sender, receiver = tgcrypto.TransportCodec(transport), tgcrypto.TransportCodec(transport)
+            stream = b"".join(sender.encode(packet) for packet in packets)
+
+            frames = receiver.feed(stream[:50]) + receiver.feed(stream[50:])
+            self.assertEqual(len(frames), len(packets))
+            for frame, packet in zip(frames, packets):
+                self.assertEqual(frame[:len(packet)], packet)
+
+    def test_header(self):
+        self.assertEqual(tgcrypto.TransportCodec("abridged").header, b"\xef")
+        self.assertEqual(tgcrypto.TransportCodec("intermediate").header, b"\xee" * 4)
+        self.assertEqual(tgcrypto.TransportCodec("full").header, b"")
+
+    def test_full_crc(self):
+        frame = tgcrypto.TransportCodec("full").encode(b"abcd")
+        self.assertEqual(frame[:8], struct.pack("<ii", 16, 0))
+        self.assertEqual(frame[12:], struct.pack("<I", zlib.crc32(frame[:12])))
+
+        with self.assertRaisesRegex(ValueError, r"CRC32 mismatch"):
+            tgcrypto.TransportCodec("full").feed(frame[:-1] + bytes([frame[-1] ^ 1]))
+
+    def test_error_code(self):
+        codec = tgcrypto.TransportCodec("intermediate")
+        self.assertEqual(codec.feed(struct.pack("<ii", 4, -404)), [-404])
+
+    def test_quick_ack(self):
+        token = 0x80000000 | 0x1234567
+
+        codec = tgcrypto.TransportCodec("abridged")
+        self.assertEqual(codec.encode(b"\x00" * 8, quick_ack=True)[0], 0x82)
+        self.assertEqual(codec.feed(token.to_bytes(4, "big")), [token])
+
+        codec = tgcrypto.TransportCodec("intermediate")
+        self.assertEqual(codec.encode(b"\x00" * 8, quick_ack=True)[:4], struct.pack("<I", 8 | 1 << 31))
+        self.assertEqual(codec.feed(struct.pack("<I", token)), [token])
+
+    def test_unknown_transport(self):
+        with self.assertRaisesRegex(ValueError, r"Unknown transport"):
+            tgcrypto.TransportCodec("http")
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -2,10 +2,135 @@ def ige256_encrypt(data: bytes, key: bytes, iv: bytes | bytearray) -> bytes:
     """AES-256-IGE Encryption"""
 def ige256_decrypt(data: bytes, key: bytes, iv: bytes | bytearray) -> bytes:
     """AES-256-IGE Decryption"""
+class IgeCipher:
+    """AES-256-IGE cipher holding an expanded key"""
+    def __init__(self, key: bytes, /) -> None: ...
+    def encrypt(self, data: bytes, iv: bytes, /) -> bytes:
+        """AES-256-IGE Encryption"""
+    def decrypt(self, data: bytes, iv: bytes, /) -> bytes:
+        """AES-256-IGE Decryption"""
+    def encryptor(self, iv: bytes, /) -> IgeStream:
+        """Start a resumable encryption for a payload processed in chunks"""
+    def decryptor(self, iv: bytes, /) -> IgeStream:
+        """Start a resumable decryption for a payload processed in chunks"""
+class IgeStream:
+    """Resumable AES-256-IGE state carrying (iv1, iv2) across chunks"""
+    @property
+    def iv(self) -> bytes:
+        """Current 32-byte IV, usable to continue with ige256_encrypt/ige256_decrypt"""
+    def update(self, data: bytes, /) -> bytes:
+        """Process the next chunk, its length must be a multiple of 16"""
 def ctr256_encrypt(data: bytes, key: bytes, iv: bytes | bytearray, state: bytes | bytearray) -> bytes:
     """AES-256-CTR Encryption"""
 def ctr256_decrypt(data: bytes, key: bytes, iv: bytes | bytearray, state: bytes | bytearray) -> bytes:
     """AES-256-CTR Decryption"""
+class CtrStream:
+    """AES-256-CTR stream owning its counter and keystream position"""
+    def __init__(self, key: bytes, iv: bytes, offset: int = 0) -> None: ...
+    def process(self, data: bytes, /) -> bytes:
+        """Encrypt or decrypt the next bytes of the stream"""
+    def seek(self, offset: int, /) -> None:
+        """Move to an absolute byte offset in the keystream"""
+    def tell(self) -> int:
+        """Current byte offset in the keystream"""
+class TransportCodec:
+    """Sans-IO framing for the abridged, intermediate, padded intermediate and full transports"""
+    def __init__(self, transport: str) -> None: ...
+    @property
+    def header(self) -> bytes:
+        """Bytes a client sends first on a plain TCP connection"""
+    def encode(self, packet: bytes, /, quick_ack: bool = False) -> bytes:
+        """Frame an outgoing packet, optionally requesting a quick ACK"""
+    def feed(self, data: bytes, /) -> list[bytes | int]:
+        """Feed received bytes and return every complete packet
+        Transport errors are returned as negative ints and quick-ACK tokens as ints >= 2**31"""
+class AuthKeyHandshake:
+    """Sans-IO auth key creation, from req_pq_multi to dh_gen_ok
+    Messages are plain MTProto messages, to be framed with a transport codec"""
+    def __init__(self, dc_id: int, expires_in: int | None = None) -> None: ...
+    def start(self) -> bytes:
+        """Start (or restart) the handshake and return req_pq_multi"""
+    def feed(self, packet: bytes, /) -> bytes | None:
+        """Process a server message and return the next one to send, or None once the key is created"""
+    @property
+    def done(self) -> bool:
+        """Whether the auth key has been created"""
+    @property
+    def auth_key(self) -> bytes | None: ...
+    @property
+    def auth_key_id(self) -> bytes | None: ...
+    @property
+    def server_salt(self) -> int | None: ...
+    @property
+    def time_offset(self) -> int | None:
+        """Server time minus local time, in seconds"""
+    @property
+    def expires_at(self) -> int | None:
+        """Server time a temporary key expires at"""
+class Obfuscated2:
+    """Client side of the obfuscated2 transport"""
+    def __init__(self, tag: bytes, dc_id: int, secret: bytes | None = None) -> None: ...
+    @property
+    def init_packet(self) -> bytes:
+        """The 64 bytes to send before any other data"""
+    @property
+    def encryptor(self) -> CtrStream:
+        """Stream for outgoing data, already advanced past the init header"""
+    @property
+    def decryptor(self) -> CtrStream:
+        """Stream for incoming data"""
+class Obfuscated2Server:
+    """Server side of the obfuscated2 transport"""
+    def __init__(self, init_packet: bytes, secrets: list[bytes]) -> None: ...
+    @property
+    def secret_index(self) -> int:
+        """Index of the secret the client used"""
+    @property
+    def tag(self) -> bytes:
+        """Protocol tag requested by the client"""
+    @property
+    def dc_id(self) -> int:
+        """DC id requested by the client"""
+    @property
+    def encryptor(self) -> CtrStream:
+        """Stream for outgoing data"""
+    @property
+    def decryptor(self) -> CtrStream:
+        """Stream for incoming data, already advanced past the init header"""
+class FakeTlsClient:
+    """Client side of the MTProxy fake-TLS transport"""
+    def __init__(self, secret: bytes, tag: bytes, dc_id: int) -> None: ...
+    @property
+    def client_hello(self) -> bytes:
+        """The ClientHello record to send first"""
+    def verify_server_hello(self, response: bytes, /) -> int | None:
+        """Check the server's response to the ClientHello"""
+    def encrypt(self, data: bytes, /) -> bytes:
+        """Encrypt outgoing data and wrap it in application data records"""
+    def decrypt(self, data: bytes, /) -> bytes:
+        """Unwrap incoming records and decrypt their payload"""
+class FakeTlsServer:
+    """Server side of the MTProxy fake-TLS transport"""
+    def __init__(self, client_hello: bytes, secrets: list[bytes], max_skew: int = 120) -> None: ...
+    @property
+    def secret_index(self) -> int:
+        """Index of the secret the client used"""
+    @property
+    def timestamp(self) -> int:
+        """Unix time the client put in its ClientHello"""
+    @property
+    def server_hello(self) -> bytes:
+        """The records to send back in reply to the ClientHello"""
+    @property
+    def tag(self) -> bytes | None:
+        """Protocol tag requested by the client, or None before the init header"""
+    @property
+    def dc_id(self) -> int | None:
+        """DC id requested by the client, or None before the init header"""
+    def encrypt(self, data: bytes, /) -> bytes:
+        """Encrypt outgoing data and wrap it in application data records"""
+    def decrypt(self, data: bytes, /) -> bytes:
+        """Unwrap incoming records and decrypt their payload"""
 def cbc256_encrypt(data: bytes, key: bytes, iv: bytes | bytearray) -> bytes:
     """AES-256-CBC Encryption"""
 def cbc256_decrypt(data: bytes, key: bytes, iv: bytes | bytearray) -> bytes:
@@ -14,9 +139,86 @@ def sha1(data: bytes) -> bytes:
     """Compute SHA-1 hash"""
 def sha256(data: bytes) -> bytes:
     """Compute SHA-256 hash"""
-def rsa_encrypt(data: bytes, fingerprint: int) -> bytes:
+def rsa_encrypt(data: bytes, fingerprint: int, /, padding: str | None = None) -> bytes:
     """RSA encrypt using Telegram server public key"""
-def factorize(pq: int) -> int:
-    """Find a non-trivial factor using Pollard's rho algorithm"""
+def rsa_pad_encrypt(data: bytes, fingerprint: int, /) -> bytes:
+    """RSA_PAD encryption of p_q_inner_data for req_DH_params"""
+def add_public_key(pem: str, /) -> int:
+    """Register a PEM encoded RSA public key and return its fingerprint"""
+def remove_public_key(fingerprint: int, /) -> bool:
+    """Unregister an RSA public key, returning whether it was registered"""
+def list_public_keys() -> list[int]:
+    """Fingerprints of every registered RSA public key"""
+def add_test_dc_keys() -> list[int]:
+    """Register the test DC public keys and return their fingerprints"""
+def rsa_fingerprint(pem: str, /) -> int:
+    """Telegram fingerprint of a PKCS#1 or SPKI PEM encoded RSA public key"""
+class IterationLimitError(ValueError):
+    """Factorization used up its max_iterations, seed replays the run"""
+    iterations: int
+    seed: int
+def factorize(pq: int | bytes, /, seed: int | None = None, max_iterations: int | None = None) -> tuple[int, int]:
+    """Split pq into its two factors using Pollard's rho algorithm
+    Returns (p, q) with p <= q"""
+def factorize_with_iterations(
+    pq: int | bytes, /, seed: int | None = None, max_iterations: int | None = None
+) -> tuple[int, int, int, int]:
+    """factorize that also returns the number of iterations it took and the seed that replays the run
+    Returns (p, q, iterations, seed)"""
+def is_prime(n: int | bytes, /) -> bool:
+    """Deterministic primality test, Miller-Rabin below 2^64 and Baillie-PSW above"""
+def factor_all(
+    n: int | bytes, /, seed: int | None = None, max_iterations: int | None = None
+) -> list[tuple[int, int]]:
+    """Factorize n completely, with the same seed and max_iterations as factorize
+    Returns [(prime, exponent), ...] in increasing order of the primes"""
 def get_session_id(auth_key: bytes) -> bytes:
     """Generate session ID from auth key"""
+def auth_key_aux_hash(auth_key: bytes, /) -> bytes:
+    """First 8 bytes of SHA1(auth_key)"""
+def new_nonce_hash(new_nonce: bytes, auth_key: bytes, number: int, /) -> bytes:
+    """new_nonce_hash1/2/3 expected in dh_gen_ok/retry/fail"""
+def check_new_nonce_hash(new_nonce: bytes, auth_key: bytes, number: int, hash: bytes, /) -> None:
+    """Raise ValueError unless hash is new_nonce_hash<number>, compared in constant time"""
+def server_salt(new_nonce: bytes, server_nonce: bytes, /) -> int:
+    """Initial server_salt: new_nonce[0:8] XOR server_nonce[0:8]"""
+def decrypt_server_message(
+    auth_key: bytes, packet: bytes, session_id: bytes, time_offset: int = 0
+) -> tuple[int, int, int, bytes]:
+    """Decrypt a message sent by the server and validate it before returning anything"""
+def encrypt_message(auth_key: bytes, plaintext: bytes, x: int, /) -> bytes:
+    """MTProto 2.0 message encryption"""
+def encrypt_message_quick_ack(auth_key: bytes, plaintext: bytes, x: int, /) -> tuple[bytes, int]:
+    """MTProto 2.0 message encryption that also returns the expected quick-ACK token"""
+def decrypt_message(auth_key: bytes, packet: bytes, x: int, /) -> bytes:
+    """MTProto 2.0 message decryption"""
+def kdf_v1(auth_key: bytes, msg_key: bytes, x: int, /) -> tuple[bytes, bytes]:
+    """MTProto 1.0 KDF"""
+def encrypt_message_v1(auth_key: bytes, plaintext: bytes, x: int, /) -> bytes:
+    """MTProto 1.0 message encryption"""
+def decrypt_message_v1(auth_key: bytes, packet: bytes, x: int, /) -> bytes:
+    """MTProto 1.0 message decryption"""
+def encrypt_secret_message_v1(auth_key: bytes, payload: bytes, x: int = 0, /) -> bytes:
+    """MTProto 1.0 secret chat encryption, prefixing the payload with its int32 length"""
+def decrypt_secret_message_v1(auth_key: bytes, packet: bytes, x: int = 0, /) -> bytes:
+    """MTProto 1.0 secret chat decryption, returning the payload without its length prefix"""
+def tmp_aes_key_iv(new_nonce: bytes, server_nonce: bytes, /) -> tuple[bytes, bytes]:
+    """Temporary AES key and IV protecting the DH exchange"""
+def decrypt_server_dh_inner_data(encrypted_answer: bytes, new_nonce: bytes, server_nonce: bytes, /) -> bytes:
+    """Decrypt the encrypted_answer of server_DH_params_ok and check its SHA-1"""
+def encrypt_client_dh_inner_data(data: bytes, new_nonce: bytes, server_nonce: bytes, /) -> bytes:
+    """Encrypt client_DH_inner_data for set_client_DH_params"""
+def encrypt_bind_temp_auth_key(
+    perm_key: bytes, temp_key_id: int, nonce: int, expires_at: int, temp_session_id: int, /, msg_id: int | None = None
+) -> bytes:
+    """encrypted_message for auth.bindTempAuthKey, encrypted with the permanent key using MTProto 1.0"""
+def check_dh_prime(dh_prime: bytes, /) -> None:
+    """Raise ValueError unless dh_prime is a 2048-bit safe prime"""
+def check_dh_params(dh_prime: bytes, g: int, /) -> None:
+    """Check dh_prime and that g is a valid generator for it"""
+def check_g_a(g_a: bytes, dh_prime: bytes, /) -> None:
+    """Raise ValueError unless g_a lies in [2^1984, dh_prime - 2^1984]"""
+def compute_g_b(g: int, b: bytes, dh_prime: bytes, /) -> bytes:
+    """Compute g^b mod dh_prime as 256 big-endian bytes"""
+def compute_auth_key(g_a: bytes, b: bytes, dh_prime: bytes, /) -> bytes:
+    """Compute auth_key = g_a^b mod dh_prime as 256 big-endian bytes"""